@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{converter::Pair, series::RateChange};
+
+/// A rule that triggers once the rate of a pair moves past a threshold
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub pair: Pair,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Condition {
+    Above(f64),
+    Below(f64),
+    /// A move of at least this many percent, in either direction, since the
+    /// previously stored rate
+    Change(f64),
+}
+
+impl Alert {
+    /// Returns a message describing why the alert triggered, if it did
+    pub fn check(&self, rate: f64, previous: Option<f64>) -> Option<String> {
+        let triggered = match self.condition {
+            Condition::Above(threshold) => rate > threshold,
+            Condition::Below(threshold) => rate < threshold,
+            Condition::Change(percent) => match previous {
+                Some(previous) => RateChange::new(previous, rate).percent.abs() >= percent,
+                None => false,
+            },
+        };
+        if !triggered {
+            return None;
+        }
+
+        let detail = match (self.condition, previous) {
+            (Condition::Change(_), Some(previous)) => format!(
+                "moved {:+.2}% from {}",
+                RateChange::new(previous, rate).percent,
+                previous
+            ),
+            _ => format!("is {}", rate),
+        };
+        Some(format!("{} {} ({})", self.pair, detail, self.condition))
+    }
+}
+
+impl Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.pair, self.condition)
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Above(threshold) => write!(f, "above {}", threshold),
+            Condition::Below(threshold) => write!(f, "below {}", threshold),
+            Condition::Change(percent) => write!(f, "changes by {}%", percent),
+        }
+    }
+}
+
+/// Parses a percentage, with or without a trailing `%`
+pub fn parse_percent(value: &str) -> Result<f64, String> {
+    let percent: f64 = value
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("invalid percentage `{}`", value))?;
+    if percent <= 0.0 {
+        return Err("the percentage must be positive".to_string());
+    }
+    Ok(percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(condition: Condition) -> Alert {
+        Alert {
+            pair: "USD/PLN".parse().unwrap(),
+            condition,
+        }
+    }
+
+    #[test]
+    fn check_thresholds() {
+        assert!(alert(Condition::Above(4.1)).check(4.11, None).is_some());
+        assert!(alert(Condition::Above(4.1)).check(4.1, None).is_none());
+        assert!(alert(Condition::Below(4.0)).check(3.99, None).is_some());
+        assert!(alert(Condition::Below(4.0)).check(4.01, None).is_none());
+    }
+
+    #[test]
+    fn check_change() {
+        let alert = alert(Condition::Change(2.0));
+        assert!(alert.check(4.1, None).is_none());
+        assert!(alert.check(4.05, Some(4.0)).is_none());
+        assert!(alert.check(4.08, Some(4.0)).is_some());
+        let message = alert.check(3.9, Some(4.0)).unwrap();
+        assert_eq!(message, "USD/PLN moved -2.50% from 4 (changes by 2%)");
+    }
+
+    #[test]
+    fn parse_percentages() {
+        assert_eq!(parse_percent("2%"), Ok(2.0));
+        assert_eq!(parse_percent("0.5"), Ok(0.5));
+        assert!(parse_percent("abc").is_err());
+        assert!(parse_percent("-1%").is_err());
+    }
+}
@@ -1,34 +1,174 @@
-use std::{collections::HashMap, env, error::Error, fs::File, io::BufReader, time::SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    error::Error,
+    fs::File,
+    io::BufReader,
+    path::PathBuf,
+    sync::OnceLock,
+};
 
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-use crate::converter::Converter;
+use crate::{converter::Converter, helpers::now};
 
-#[derive(Serialize, Deserialize, Debug)]
+pub mod snapshot;
+
+use snapshot::{MergeStrategy, Snapshot};
+
+static NAMESPACE: OnceLock<String> = OnceLock::new();
+static RETENTION: OnceLock<Retention> = OnceLock::new();
+
+/// Keeps the cache and the other data files of a profile apart from the
+/// others, e.g. in `cache.<name>.json`
+pub fn set_namespace(name: &str) {
+    let _ = NAMESPACE.set(name.to_string());
+}
+
+/// Sets how long the observations of every pair are kept, resolved from the
+/// config file at startup
+pub fn set_retention(retention: Retention) {
+    let _ = RETENTION.set(retention);
+}
+
+fn retention() -> Retention {
+    *RETENTION.get_or_init(Retention::default)
+}
+
+/// The path of a data file kept alongside the cache, e.g. `cache.json`, or
+/// `cache.<name>.json` for a profile
+pub fn data_path(stem: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = env::current_dir()?;
+    Ok(match NAMESPACE.get() {
+        Some(name) => path.join(format!("{}.{}.json", stem, name)),
+        None => path.join(format!("{}.json", stem)),
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Cache {
     contents: HashMap<String, HashMap<String, RateEntry>>,
+    #[serde(default)]
+    history: HashMap<String, HashMap<String, Vec<Observation>>>,
+    #[serde(default)]
+    historical: BTreeMap<NaiveDate, HashMap<String, HashMap<String, RateEntry>>>,
+    #[serde(default)]
+    stats: CacheStats,
+    #[serde(skip, default = "retention")]
+    retention: Retention,
+}
+
+/// Lookup counters kept across runs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// A flattened view of a cached rate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedRate {
+    pub base: String,
+    pub target: String,
+    pub rate: f64,
+    pub fetched_at: u64,
+    pub updated_at: Option<u64>,
+    pub source: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct RateEntry {
     rate: f64,
-    last_updated_at: u64,
+    #[serde(alias = "last_updated_at")]
+    fetched_at: u64,
+    #[serde(default)]
+    updated_at: Option<u64>,
+    #[serde(default)]
+    source: String,
+}
+
+/// A single rate seen for a pair, kept after newer rates replace it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub rate: f64,
+    pub fetched_at: u64,
+    #[serde(default)]
+    pub updated_at: Option<u64>,
+    pub source: String,
+}
+
+/// Which timestamp decides whether a cached rate is stale
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Staleness {
+    /// When the rate was fetched from the source
+    #[default]
+    Fetched,
+    /// When the source last updated the rate, falling back to the fetch time
+    Provider,
+}
+
+/// How many observations are kept per pair, and for how long
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    pub max_age: u64,
+    pub max_entries: usize,
+}
+
+pub const DEFAULT_HISTORY_DAYS: u64 = 365;
+pub const DEFAULT_HISTORY_ENTRIES: usize = 1000;
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            max_age: DEFAULT_HISTORY_DAYS * 24 * 60 * 60,
+            max_entries: DEFAULT_HISTORY_ENTRIES,
+        }
+    }
 }
 
 impl RateEntry {
-    fn new(rate: f64, last_updated_at: u64) -> Self {
+    fn new(rate: f64, fetched_at: u64, updated_at: Option<u64>, source: String) -> Self {
         RateEntry {
             rate,
-            last_updated_at,
+            fetched_at,
+            updated_at,
+            source,
         }
     }
 
-    fn is_valid(&self, duration: u64) -> bool {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        now - self.last_updated_at < duration
+    fn age(&self, staleness: Staleness) -> u64 {
+        let timestamp = match staleness {
+            Staleness::Fetched => self.fetched_at,
+            Staleness::Provider => self.updated_at.unwrap_or(self.fetched_at),
+        };
+        now().saturating_sub(timestamp)
+    }
+
+    fn is_valid(&self, duration: u64, staleness: Staleness) -> bool {
+        self.age(staleness) < duration
+    }
+
+    fn to_converter(&self, base: &str, target: &str) -> Converter {
+        Converter::new(
+            base.to_string(),
+            target.to_string(),
+            self.rate,
+            self.source.clone(),
+            self.updated_at,
+        )
+    }
+}
+
+impl Observation {
+    fn new(rate: f64, fetched_at: u64, updated_at: Option<u64>, source: String) -> Self {
+        Observation {
+            rate,
+            fetched_at,
+            updated_at,
+            source,
+        }
     }
 }
 
@@ -36,12 +176,19 @@ impl Cache {
     pub fn new() -> Self {
         Cache {
             contents: HashMap::new(),
+            history: HashMap::new(),
+            historical: BTreeMap::new(),
+            stats: CacheStats::default(),
+            retention: retention(),
         }
     }
 
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        data_path("cache")
+    }
+
     pub fn load() -> Result<Self, Box<dyn Error>> {
-        let path = env::current_dir()?;
-        let path = path.join("cache.json");
+        let path = Self::path()?;
         let file = File::open(path)?;
         let reader = BufReader::new(file);
         let cache: Cache = serde_json::from_reader(reader)?;
@@ -49,23 +196,24 @@ impl Cache {
     }
 
     pub fn save(&self) -> Result<(), Box<dyn Error>> {
-        let path = env::current_dir()?;
-        let path = path.join("cache.json");
+        let path = Self::path()?;
         let file = File::create(path)?;
         serde_json::to_writer(file, self)?;
         Ok(())
     }
 
-    pub fn get(&self, base: &str, target: &str, duration: u64) -> Option<Converter> {
-        match self.contents.get(base) {
+    pub fn get(
+        &mut self,
+        base: &str,
+        target: &str,
+        duration: u64,
+        staleness: Staleness,
+    ) -> Option<Converter> {
+        let converter = match self.contents.get(base) {
             Some(target_to_rate) => match target_to_rate.get(target) {
                 Some(rate) => {
-                    if rate.is_valid(duration) {
-                        Some(Converter::new(
-                            base.to_string(),
-                            target.to_string(),
-                            rate.rate,
-                        ))
+                    if rate.is_valid(duration, staleness) {
+                        Some(rate.to_converter(base, target))
                     } else {
                         None
                     }
@@ -73,27 +221,338 @@ impl Cache {
                 None => None,
             },
             None => None,
+        };
+        match converter {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
         }
+        converter
     }
 
-    pub fn set(&mut self, converter: &Converter) {
-        let base = &converter.base;
-        let target = &converter.target;
-        let rate = &converter.rate;
-        let last_updated_at = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        match self.contents.get_mut(base) {
-            Some(target_to_rate) => {
-                target_to_rate.insert(target.to_string(), RateEntry::new(*rate, last_updated_at));
+    /// Returns the cached rate for the pair on a past date, which never
+    /// becomes stale
+    pub fn get_historical(
+        &mut self,
+        base: &str,
+        target: &str,
+        date: NaiveDate,
+    ) -> Option<Converter> {
+        let converter = self
+            .historical
+            .get(&date)
+            .and_then(|base_to_rates| base_to_rates.get(base))
+            .and_then(|target_to_rate| target_to_rate.get(target))
+            .map(|rate| rate.to_converter(base, target));
+        match converter {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        converter
+    }
+
+    /// Returns every retained observation for the pair, oldest first
+    pub fn history(&self, base: &str, target: &str) -> &[Observation] {
+        self.history
+            .get(base)
+            .and_then(|target_to_history| target_to_history.get(target))
+            .map(|observations| observations.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the two newest observations of a pair, the previous one first.
+    /// Right after the rate is fetched, the newest one is the current rate
+    pub fn last_two(&self, base: &str, target: &str) -> Option<(&Observation, &Observation)> {
+        match self.history(base, target) {
+            [.., previous, current] => Some((previous, current)),
+            _ => None,
+        }
+    }
+
+    pub fn set_historical(&mut self, converter: &Converter, date: NaiveDate) {
+        let entry = RateEntry::new(
+            converter.rate,
+            now(),
+            converter.updated_at,
+            converter.source.clone(),
+        );
+        self.historical
+            .entry(date)
+            .or_default()
+            .entry(converter.base.clone())
+            .or_default()
+            .insert(converter.target.clone(), entry);
+    }
+
+    /// Returns one rate per day between two dates, inclusive, preferring the
+    /// rates cached for past dates and otherwise the last observation made
+    /// on that day
+    pub fn daily_rates(
+        &self,
+        base: &str,
+        target: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> BTreeMap<NaiveDate, f64> {
+        let mut rates = BTreeMap::new();
+        if let Some(observations) = self
+            .history
+            .get(base)
+            .and_then(|target_to_history| target_to_history.get(target))
+        {
+            for observation in observations {
+                let timestamp = observation.updated_at.unwrap_or(observation.fetched_at);
+                let date = match DateTime::<Utc>::from_timestamp(timestamp as i64, 0) {
+                    Some(datetime) => datetime.date_naive(),
+                    None => continue,
+                };
+                if date >= from && date <= to {
+                    rates.insert(date, observation.rate);
+                }
             }
-            None => {
-                let mut target_to_rate = HashMap::new();
-                target_to_rate.insert(target.to_string(), RateEntry::new(*rate, last_updated_at));
-                self.contents.insert(base.to_string(), target_to_rate);
+        }
+
+        for (date, base_to_rates) in self.historical.range(from..=to) {
+            if let Some(rate) = base_to_rates
+                .get(base)
+                .and_then(|target_to_rate| target_to_rate.get(target))
+            {
+                rates.insert(*date, rate.rate);
+            }
+        }
+        rates
+    }
+
+    pub fn historical_count(&self) -> usize {
+        self.historical
+            .values()
+            .flat_map(|base_to_rates| base_to_rates.values())
+            .map(|target_to_rate| target_to_rate.len())
+            .sum()
+    }
+
+    /// Checks whether the pair has a cached rate within the cache duration,
+    /// without counting it as a lookup
+    pub fn is_fresh(&self, base: &str, target: &str, duration: u64, staleness: Staleness) -> bool {
+        self.contents
+            .get(base)
+            .and_then(|target_to_rate| target_to_rate.get(target))
+            .is_some_and(|rate| rate.is_valid(duration, staleness))
+    }
+
+    /// Returns the newest cached rate for the pair regardless of the cache
+    /// duration, together with its age in seconds, as long as it is not older
+    /// than `max_age`
+    pub fn get_stale(
+        &self,
+        base: &str,
+        target: &str,
+        max_age: Option<u64>,
+        staleness: Staleness,
+    ) -> Option<(Converter, u64)> {
+        let rate = self.contents.get(base)?.get(target)?;
+        let age = rate.age(staleness);
+        match max_age {
+            Some(max_age) if age > max_age => None,
+            _ => Some((rate.to_converter(base, target), age)),
+        }
+    }
+
+    /// Returns every currency code the cache holds a rate for, sorted
+    pub fn currencies(&self) -> Vec<String> {
+        let mut currencies: Vec<String> = self
+            .contents
+            .iter()
+            .flat_map(|(base, target_to_rate)| {
+                std::iter::once(base.to_string()).chain(target_to_rate.keys().cloned())
+            })
+            .collect();
+        currencies.sort();
+        currencies.dedup();
+        currencies
+    }
+
+    /// Returns every cached rate, sorted by base and target
+    pub fn rates(&self) -> Vec<CachedRate> {
+        let mut rates: Vec<CachedRate> = self
+            .contents
+            .iter()
+            .flat_map(|(base, target_to_rate)| {
+                target_to_rate.iter().map(|(target, rate)| CachedRate {
+                    base: base.to_string(),
+                    target: target.to_string(),
+                    rate: rate.rate,
+                    fetched_at: rate.fetched_at,
+                    updated_at: rate.updated_at,
+                    source: rate.source.clone(),
+                })
+            })
+            .collect();
+        rates.sort_by(|a, b| (&a.base, &a.target).cmp(&(&b.base, &b.target)));
+        rates
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn observation_count(&self) -> usize {
+        self.history
+            .values()
+            .flat_map(|target_to_history| target_to_history.values())
+            .map(|observations| observations.len())
+            .sum()
+    }
+
+    /// Removes cached rates and their history, either all of them, all for a
+    /// base currency or a single pair, returning how many rates were removed
+    pub fn clear(&mut self, base: Option<&str>, target: Option<&str>) -> usize {
+        let mut removed = Self::remove_pairs(&mut self.contents, base, target);
+        for base_to_rates in self.historical.values_mut() {
+            removed += Self::remove_pairs(base_to_rates, base, target);
+        }
+        self.historical
+            .retain(|_, base_to_rates| !base_to_rates.is_empty());
+        Self::remove_pairs(&mut self.history, base, target);
+        removed
+    }
+
+    fn remove_pairs<T>(
+        map: &mut HashMap<String, HashMap<String, T>>,
+        base: Option<&str>,
+        target: Option<&str>,
+    ) -> usize {
+        let removed = match (base, target) {
+            (None, _) => {
+                let removed = map.values().map(|x| x.len()).sum();
+                map.clear();
+                removed
+            }
+            (Some(base), None) => map.remove(base).map(|x| x.len()).unwrap_or(0),
+            (Some(base), Some(target)) => match map.get_mut(base) {
+                Some(target_to_value) => target_to_value.remove(target).is_some() as usize,
+                None => 0,
+            },
+        };
+        map.retain(|_, target_to_value| !target_to_value.is_empty());
+        removed
+    }
+
+    /// Removes cached rates and observations fetched more than `max_age`
+    /// seconds ago, returning how many rates were removed. Rates for past
+    /// dates never change, so they are kept
+    pub fn prune(&mut self, max_age: u64) -> usize {
+        let cutoff = now().saturating_sub(max_age);
+        let mut removed = 0;
+        for target_to_rate in self.contents.values_mut() {
+            let before = target_to_rate.len();
+            target_to_rate.retain(|_, rate| rate.fetched_at >= cutoff);
+            removed += before - target_to_rate.len();
+        }
+        self.contents
+            .retain(|_, target_to_rate| !target_to_rate.is_empty());
+
+        for target_to_history in self.history.values_mut() {
+            for observations in target_to_history.values_mut() {
+                observations.retain(|observation| observation.fetched_at >= cutoff);
+            }
+            target_to_history.retain(|_, observations| !observations.is_empty());
+        }
+        self.history
+            .retain(|_, target_to_history| !target_to_history.is_empty());
+        removed
+    }
+
+    pub fn set(&mut self, converter: &Converter) {
+        let entry = RateEntry::new(
+            converter.rate,
+            now(),
+            converter.updated_at,
+            converter.source.clone(),
+        );
+        self.record(&converter.base, &converter.target, entry);
+    }
+
+    /// Builds a portable snapshot of every cached rate
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            exported_at: now(),
+            rates: self.rates(),
+        }
+    }
+
+    /// Merges the rates of a snapshot into the cache, returning how many
+    /// were imported
+    pub fn import(&mut self, snapshot: &Snapshot, strategy: MergeStrategy) -> usize {
+        let mut imported = 0;
+        for rate in &snapshot.rates {
+            let existing = self
+                .contents
+                .get(&rate.base)
+                .and_then(|target_to_rate| target_to_rate.get(&rate.target));
+            let accept = match (existing, strategy) {
+                (None, _) => true,
+                (Some(_), MergeStrategy::KeepExisting) => false,
+                (Some(existing), MergeStrategy::Newest) => {
+                    rate.updated_at.unwrap_or(rate.fetched_at)
+                        > existing.updated_at.unwrap_or(existing.fetched_at)
+                }
+            };
+            if accept {
+                let entry = RateEntry::new(
+                    rate.rate,
+                    rate.fetched_at,
+                    rate.updated_at,
+                    rate.source.clone(),
+                );
+                self.record(&rate.base, &rate.target, entry);
+                imported += 1;
             }
         }
+        imported
+    }
+
+    fn record(&mut self, base: &str, target: &str, entry: RateEntry) {
+        let observation = Observation::new(
+            entry.rate,
+            entry.fetched_at,
+            entry.updated_at,
+            entry.source.clone(),
+        );
+        self.contents
+            .entry(base.to_string())
+            .or_default()
+            .insert(target.to_string(), entry);
+
+        let observations = self
+            .history
+            .entry(base.to_string())
+            .or_default()
+            .entry(target.to_string())
+            .or_default();
+        // A rate the source has not updated since the previous fetch is only
+        // recorded once
+        let index = observations.partition_point(|x| x.fetched_at <= observation.fetched_at);
+        let unchanged = index
+            .checked_sub(1)
+            .map(|x| &observations[x])
+            .is_some_and(|x| {
+                x.rate == observation.rate
+                    && x.updated_at == observation.updated_at
+                    && x.source == observation.source
+            });
+        if !unchanged {
+            observations.insert(index, observation);
+        }
+        Self::retain(observations, &self.retention);
+    }
+
+    fn retain(observations: &mut Vec<Observation>, retention: &Retention) {
+        let cutoff = now().saturating_sub(retention.max_age);
+        observations.retain(|observation| observation.fetched_at >= cutoff);
+        if observations.len() > retention.max_entries {
+            let excess = observations.len() - retention.max_entries;
+            observations.drain(..excess);
+        }
     }
 }
 
@@ -101,18 +560,31 @@ impl Cache {
 mod tests {
     use super::*;
 
+    fn converter(rate: f64) -> Converter {
+        Converter::new(
+            "USD".to_string(),
+            "EUR".to_string(),
+            rate,
+            "test".to_string(),
+            None,
+        )
+    }
+
     #[test]
     fn cache_get() {
         let mut cache = Cache::new();
-        let converter = Converter::new("USD".to_string(), "EUR".to_string(), 0.85);
+        let converter = converter(0.85);
         cache.set(&converter);
-        assert_eq!(cache.get("USD", "EUR", 60), Some(converter));
+        assert_eq!(
+            cache.get("USD", "EUR", 60, Staleness::Fetched),
+            Some(converter)
+        );
     }
 
     #[test]
     fn cache_set() {
         let mut cache = Cache::new();
-        let converter = Converter::new("USD".to_string(), "EUR".to_string(), 0.85);
+        let converter = converter(0.85);
         cache.set(&converter);
         assert_eq!(cache.contents.len(), 1);
         assert_eq!(cache.contents.get("USD").unwrap().len(), 1);
@@ -122,19 +594,329 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cache_set_keeps_history() {
+        let mut cache = Cache::new();
+        cache.set(&converter(0.85));
+        cache.set(&converter(0.86));
+        let history = cache.history("USD", "EUR");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].rate, 0.85);
+        assert_eq!(history[1].rate, 0.86);
+        assert_eq!(history[1].source, "test");
+        assert_eq!(
+            cache
+                .get("USD", "EUR", 60, Staleness::Fetched)
+                .unwrap()
+                .rate,
+            0.86
+        );
+    }
+
+    #[test]
+    fn cache_history_retention() {
+        let mut cache = Cache::new();
+        cache.retention = Retention {
+            max_age: 60,
+            max_entries: 2,
+        };
+        for rate in [0.85, 0.86, 0.87] {
+            cache.set(&converter(rate));
+        }
+        let rates: Vec<f64> = cache.history("USD", "EUR").iter().map(|x| x.rate).collect();
+        assert_eq!(rates, vec![0.86, 0.87]);
+
+        cache
+            .history
+            .get_mut("USD")
+            .unwrap()
+            .get_mut("EUR")
+            .unwrap()[0]
+            .fetched_at = 0;
+        cache.set(&converter(0.88));
+        let rates: Vec<f64> = cache.history("USD", "EUR").iter().map(|x| x.rate).collect();
+        assert_eq!(rates, vec![0.87, 0.88]);
+    }
+
+    #[test]
+    fn cache_history_unchanged() {
+        let mut cache = Cache::new();
+        for rate in [0.85, 0.85, 0.86, 0.85] {
+            cache.set(&converter(rate));
+        }
+        let rates: Vec<f64> = cache.history("USD", "EUR").iter().map(|x| x.rate).collect();
+        assert_eq!(rates, vec![0.85, 0.86, 0.85]);
+        let (previous, current) = cache.last_two("USD", "EUR").unwrap();
+        assert_eq!((previous.rate, current.rate), (0.86, 0.85));
+    }
+
+    #[test]
+    fn cache_history_missing_pair() {
+        let cache = Cache::new();
+        assert!(cache.history("USD", "EUR").is_empty());
+        assert!(cache.last_two("USD", "EUR").is_none());
+    }
+
+    #[test]
+    fn cache_get_provider_staleness() {
+        let mut cache = Cache::new();
+        let mut converter = converter(0.85);
+        converter.updated_at = Some(now() - 3600);
+        cache.set(&converter);
+        assert!(cache.get("USD", "EUR", 60, Staleness::Fetched).is_some());
+        assert_eq!(cache.get("USD", "EUR", 60, Staleness::Provider), None);
+        assert_eq!(
+            cache.get("USD", "EUR", 7200, Staleness::Provider),
+            Some(converter)
+        );
+    }
+
+    #[test]
+    fn cache_historical() {
+        let mut cache = Cache::new();
+        let date = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        assert_eq!(cache.get_historical("USD", "EUR", date), None);
+        cache.set_historical(&converter(0.92), date);
+        assert_eq!(
+            cache.get_historical("USD", "EUR", date),
+            Some(converter(0.92))
+        );
+        assert_eq!(
+            cache.get_historical("USD", "EUR", date.pred_opt().unwrap()),
+            None
+        );
+        assert_eq!(cache.get("USD", "EUR", 60, Staleness::Fetched), None);
+        assert_eq!(cache.historical_count(), 1);
+        assert_eq!(cache.prune(0), 0);
+        assert_eq!(cache.historical_count(), 1);
+        assert_eq!(cache.clear(Some("USD"), Some("EUR")), 1);
+        assert_eq!(cache.historical_count(), 0);
+    }
+
+    #[test]
+    fn cache_daily_rates() {
+        let mut cache = Cache::new();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let timestamp = |day, hour| {
+            date(day)
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp() as u64
+        };
+        for (rate, updated_at) in [
+            (0.90, timestamp(1, 8)),
+            (0.91, timestamp(1, 20)),
+            (0.95, timestamp(3, 8)),
+        ] {
+            let mut converter = converter(rate);
+            converter.updated_at = Some(updated_at);
+            cache.set(&converter);
+        }
+        cache.set_historical(&converter(0.92), date(2));
+        cache.set_historical(&converter(0.93), date(3));
+        cache.set_historical(&converter(0.99), date(4));
+
+        let rates = cache.daily_rates("USD", "EUR", date(1), date(3));
+        assert_eq!(
+            rates.into_iter().collect::<Vec<_>>(),
+            vec![(date(1), 0.91), (date(2), 0.92), (date(3), 0.93)]
+        );
+        assert!(cache.daily_rates("USD", "GBP", date(1), date(3)).is_empty());
+    }
+
+    #[test]
+    fn cache_is_fresh() {
+        let mut cache = Cache::new();
+        assert!(!cache.is_fresh("USD", "EUR", 60, Staleness::Fetched));
+        cache.set(&converter(0.85));
+        assert!(cache.is_fresh("USD", "EUR", 60, Staleness::Fetched));
+        assert!(!cache.is_fresh("USD", "EUR", 0, Staleness::Fetched));
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn cache_get_stale() {
+        let mut cache = Cache::new();
+        let converter = converter(0.85);
+        cache.set(&converter);
+        cache
+            .contents
+            .get_mut("USD")
+            .unwrap()
+            .get_mut("EUR")
+            .unwrap()
+            .fetched_at -= 600;
+        assert_eq!(cache.get("USD", "EUR", 300, Staleness::Fetched), None);
+
+        let (stale, age) = cache
+            .get_stale("USD", "EUR", None, Staleness::Fetched)
+            .unwrap();
+        assert_eq!(stale, converter);
+        assert!(age >= 600);
+        assert!(cache
+            .get_stale("USD", "EUR", Some(3600), Staleness::Fetched)
+            .is_some());
+        assert!(cache
+            .get_stale("USD", "EUR", Some(300), Staleness::Fetched)
+            .is_none());
+        assert!(cache
+            .get_stale("USD", "GBP", None, Staleness::Fetched)
+            .is_none());
+    }
+
+    #[test]
+    fn cache_currencies() {
+        let mut cache = Cache::new();
+        assert!(cache.currencies().is_empty());
+        cache.set(&converter(0.85));
+        let mut converter = converter(4.0);
+        converter.base = "EUR".to_string();
+        converter.target = "PLN".to_string();
+        cache.set(&converter);
+        assert_eq!(cache.currencies(), vec!["EUR", "PLN", "USD"]);
+    }
+
+    #[test]
+    fn cache_counts_lookups() {
+        let mut cache = Cache::new();
+        cache.get("USD", "EUR", 60, Staleness::Fetched);
+        cache.set(&converter(0.85));
+        cache.get("USD", "EUR", 60, Staleness::Fetched);
+        cache.get("USD", "EUR", 60, Staleness::Fetched);
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 1 });
+    }
+
+    #[test]
+    fn cache_rates() {
+        let mut cache = Cache::new();
+        cache.set(&converter(0.85));
+        let rates = cache.rates();
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].base, "USD");
+        assert_eq!(rates[0].target, "EUR");
+        assert_eq!(rates[0].rate, 0.85);
+        assert_eq!(rates[0].source, "test");
+    }
+
+    #[test]
+    fn cache_clear() {
+        let mut cache = Cache::new();
+        let mut gbp = converter(0.75);
+        gbp.target = "GBP".to_string();
+        let mut pln = converter(4.0);
+        pln.base = "EUR".to_string();
+        pln.target = "PLN".to_string();
+        for converter in [&converter(0.85), &gbp, &pln] {
+            cache.set(converter);
+        }
+
+        assert_eq!(cache.clear(Some("USD"), Some("GBP")), 1);
+        assert!(cache.history("USD", "GBP").is_empty());
+        assert_eq!(cache.clear(Some("USD"), Some("GBP")), 0);
+        assert_eq!(cache.clear(Some("USD"), None), 1);
+        assert!(!cache.contents.contains_key("USD"));
+        assert!(!cache.history.contains_key("USD"));
+        assert_eq!(cache.clear(None, None), 1);
+        assert!(cache.rates().is_empty());
+        assert_eq!(cache.observation_count(), 0);
+    }
+
+    #[test]
+    fn cache_prune() {
+        let mut cache = Cache::new();
+        let mut gbp = converter(0.75);
+        gbp.target = "GBP".to_string();
+        cache.set(&converter(0.85));
+        cache.set(&gbp);
+        cache
+            .contents
+            .get_mut("USD")
+            .unwrap()
+            .get_mut("GBP")
+            .unwrap()
+            .fetched_at = 0;
+        cache
+            .history
+            .get_mut("USD")
+            .unwrap()
+            .get_mut("GBP")
+            .unwrap()[0]
+            .fetched_at = 0;
+
+        assert_eq!(cache.prune(3600), 1);
+        assert_eq!(cache.rates().len(), 1);
+        assert_eq!(cache.observation_count(), 1);
+        assert!(!cache.history.get("USD").unwrap().contains_key("GBP"));
+    }
+
+    #[test]
+    fn cache_import_newest() {
+        let mut cache = Cache::new();
+        cache.set(&converter(0.85));
+        let mut snapshot = cache.snapshot();
+        snapshot.rates[0].rate = 0.86;
+        snapshot.rates[0].fetched_at -= 60;
+        assert_eq!(cache.import(&snapshot, MergeStrategy::Newest), 0);
+
+        snapshot.rates[0].fetched_at += 120;
+        assert_eq!(cache.import(&snapshot, MergeStrategy::Newest), 1);
+        assert_eq!(cache.rates()[0].rate, 0.86);
+        assert_eq!(cache.rates()[0].fetched_at, snapshot.rates[0].fetched_at);
+        assert_eq!(cache.history("USD", "EUR").len(), 2);
+        assert_eq!(cache.import(&snapshot, MergeStrategy::Newest), 0);
+    }
+
+    #[test]
+    fn cache_import_keep_existing() {
+        let mut cache = Cache::new();
+        cache.set(&converter(0.85));
+        let mut snapshot = cache.snapshot();
+        snapshot.rates[0].rate = 0.86;
+        snapshot.rates[0].fetched_at += 60;
+        let mut gbp = snapshot.rates[0].clone();
+        gbp.target = "GBP".to_string();
+        snapshot.rates.push(gbp);
+
+        assert_eq!(cache.import(&snapshot, MergeStrategy::KeepExisting), 1);
+        let rates = cache.rates();
+        assert_eq!(rates[0].target, "EUR");
+        assert_eq!(rates[0].rate, 0.85);
+        assert_eq!(rates[1].target, "GBP");
+    }
+
+    #[test]
+    fn cache_import_keeps_history_ordered() {
+        let mut cache = Cache::new();
+        cache.set(&converter(0.85));
+        let mut snapshot = cache.snapshot();
+        snapshot.rates[0].rate = 0.80;
+        snapshot.rates[0].fetched_at -= 60;
+        cache.contents.clear();
+
+        assert_eq!(cache.import(&snapshot, MergeStrategy::Newest), 1);
+        let rates: Vec<f64> = cache.history("USD", "EUR").iter().map(|x| x.rate).collect();
+        assert_eq!(rates, vec![0.80, 0.85]);
+    }
+
     #[test]
     fn rate_entry_is_invalid() {
-        let rate_entry = RateEntry::new(0.85, 0);
-        assert!(!rate_entry.is_valid(60));
+        let rate_entry = RateEntry::new(0.85, 0, None, "test".to_string());
+        assert!(!rate_entry.is_valid(60, Staleness::Fetched));
     }
 
     #[test]
     fn rate_entry_is_valid() {
-        let time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let rate_entry = RateEntry::new(0.85, time);
-        assert!(rate_entry.is_valid(100000));
+        let rate_entry = RateEntry::new(0.85, now(), None, "test".to_string());
+        assert!(rate_entry.is_valid(100000, Staleness::Fetched));
+        assert!(rate_entry.is_valid(100000, Staleness::Provider));
+    }
+
+    #[test]
+    fn rate_entry_reads_legacy_timestamp() {
+        let rate_entry: RateEntry =
+            serde_json::from_str(r#"{"rate":0.85,"last_updated_at":42}"#).unwrap();
+        assert_eq!(rate_entry.fetched_at, 42);
+        assert_eq!(rate_entry.updated_at, None);
     }
 }
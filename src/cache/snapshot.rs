@@ -0,0 +1,232 @@
+use std::{error::Error, path::Path};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::CachedRate;
+use crate::converter::is_code;
+
+/// A portable copy of the cached rates, used to move them between machines
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Snapshot {
+    pub exported_at: u64,
+    pub rates: Vec<CachedRate>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotFormat {
+    Json,
+    Csv,
+}
+
+/// What to do when an imported rate is already in the cache
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+pub enum MergeStrategy {
+    /// Keep whichever rate was updated most recently
+    #[default]
+    Newest,
+    /// Only import rates for pairs missing from the cache
+    KeepExisting,
+}
+
+const CSV_HEADER: &str = "base,target,rate,fetched_at,updated_at,source";
+
+impl SnapshotFormat {
+    /// Guesses the format from the file extension, defaulting to JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => SnapshotFormat::Csv,
+            _ => SnapshotFormat::Json,
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn render(&self, format: SnapshotFormat) -> Result<String, Box<dyn Error>> {
+        match format {
+            SnapshotFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            SnapshotFormat::Csv => {
+                let mut csv = format!("{}\n", CSV_HEADER);
+                for rate in &self.rates {
+                    csv.push_str(&format!(
+                        "{},{},{},{},{},{}\n",
+                        rate.base,
+                        rate.target,
+                        rate.rate,
+                        rate.fetched_at,
+                        rate.updated_at.map(|x| x.to_string()).unwrap_or_default(),
+                        rate.source
+                    ));
+                }
+                Ok(csv)
+            }
+        }
+    }
+
+    pub fn parse(contents: &str, format: SnapshotFormat) -> Result<Self, Box<dyn Error>> {
+        match format {
+            SnapshotFormat::Json => {
+                let snapshot: Snapshot = serde_json::from_str(contents)?;
+                let rates = snapshot
+                    .rates
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, rate)| {
+                        Self::check(rate).map_err(|err| format!("rate {}: {}", index + 1, err))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Snapshot {
+                    exported_at: snapshot.exported_at,
+                    rates,
+                })
+            }
+            SnapshotFormat::Csv => {
+                let mut lines = contents.lines().filter(|x| !x.trim().is_empty());
+                if lines.next().map(|x| x.trim()) != Some(CSV_HEADER) {
+                    return Err(format!("expected the CSV header to be `{}`", CSV_HEADER).into());
+                }
+
+                let mut rates = Vec::new();
+                let mut exported_at = 0;
+                for (index, line) in lines.enumerate() {
+                    let rate = Self::parse_csv_line(line)
+                        .map_err(|err| format!("line {}: {}", index + 2, err))?;
+                    exported_at = exported_at.max(rate.fetched_at);
+                    rates.push(rate);
+                }
+                Ok(Snapshot { exported_at, rates })
+            }
+        }
+    }
+
+    fn parse_csv_line(line: &str) -> Result<CachedRate, Box<dyn Error>> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() != 6 {
+            return Err(format!("expected 6 fields, found {}", fields.len()).into());
+        }
+        let rate = CachedRate {
+            base: fields[0].to_string(),
+            target: fields[1].to_string(),
+            rate: fields[2].parse()?,
+            fetched_at: fields[3].parse()?,
+            updated_at: match fields[4] {
+                "" => None,
+                value => Some(value.parse()?),
+            },
+            source: fields[5].to_string(),
+        };
+        Ok(Self::check(rate)?)
+    }
+
+    /// Uppercases the currency codes of an imported rate and rejects the
+    /// rates the cache could not be saved or used with
+    fn check(mut rate: CachedRate) -> Result<CachedRate, String> {
+        for code in [&mut rate.base, &mut rate.target] {
+            if !is_code(code) {
+                return Err(format!("invalid currency `{}`", code));
+            }
+            *code = code.to_uppercase();
+        }
+        if !rate.rate.is_finite() || rate.rate <= 0.0 {
+            return Err(format!("invalid rate `{}`", rate.rate));
+        }
+        Ok(rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            exported_at: 200,
+            rates: vec![
+                CachedRate {
+                    base: "USD".to_string(),
+                    target: "EUR".to_string(),
+                    rate: 0.85,
+                    fetched_at: 200,
+                    updated_at: Some(100),
+                    source: "currencyapi".to_string(),
+                },
+                CachedRate {
+                    base: "USD".to_string(),
+                    target: "GBP".to_string(),
+                    rate: 0.75,
+                    fetched_at: 150,
+                    updated_at: None,
+                    source: "currencyapi".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json = snapshot().render(SnapshotFormat::Json).unwrap();
+        let parsed = Snapshot::parse(&json, SnapshotFormat::Json).unwrap();
+        assert_eq!(parsed, snapshot());
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let csv = snapshot().render(SnapshotFormat::Csv).unwrap();
+        assert!(csv.starts_with(CSV_HEADER));
+        assert!(csv.contains("USD,GBP,0.75,150,,currencyapi"));
+        let parsed = Snapshot::parse(&csv, SnapshotFormat::Csv).unwrap();
+        assert_eq!(parsed, snapshot());
+    }
+
+    #[test]
+    fn csv_invalid() {
+        assert!(Snapshot::parse("USD,EUR,0.85,1,,x", SnapshotFormat::Csv).is_err());
+        let csv = format!("{}\nUSD,EUR,abc,1,,x\n", CSV_HEADER);
+        let err = Snapshot::parse(&csv, SnapshotFormat::Csv).unwrap_err();
+        assert!(err.to_string().starts_with("line 2:"));
+
+        for row in [
+            "USD,EUR,NaN,1,,x",
+            "USD,EUR,inf,1,,x",
+            "USD,EUR,-1,1,,x",
+            "U$D,EUR,1,1,,x",
+        ] {
+            let csv = format!("{}\n{}\n", CSV_HEADER, row);
+            assert!(
+                Snapshot::parse(&csv, SnapshotFormat::Csv).is_err(),
+                "{}",
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn json_invalid() {
+        let json = r#"{"exported_at": 1, "rates": [
+            {"base": "usd", "target": "eur", "rate": -0.85, "fetched_at": 1, "updated_at": null, "source": "x"}
+        ]}"#;
+        let err = Snapshot::parse(json, SnapshotFormat::Json).unwrap_err();
+        assert!(err.to_string().starts_with("rate 1:"));
+
+        let json = json.replace("-0.85", "0.85");
+        let snapshot = Snapshot::parse(&json, SnapshotFormat::Json).unwrap();
+        assert_eq!(snapshot.rates[0].base, "USD");
+        assert_eq!(snapshot.rates[0].target, "EUR");
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            SnapshotFormat::from_path(Path::new("rates.CSV")),
+            SnapshotFormat::Csv
+        );
+        assert_eq!(
+            SnapshotFormat::from_path(Path::new("rates.json")),
+            SnapshotFormat::Json
+        );
+        assert_eq!(
+            SnapshotFormat::from_path(Path::new("rates")),
+            SnapshotFormat::Json
+        );
+    }
+}
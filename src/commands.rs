@@ -1,29 +1,76 @@
+use chrono::{NaiveDate, Utc};
+
 use crate::{
-    cache::Cache,
-    helpers::error_and_exit,
-    sources::{currency_api::CurrencyApi, ConverterDataSource},
+    cache::{Cache, Staleness},
+    config::Config,
+    converter::Converter,
+    helpers::{error_and_exit, format_duration, format_timestamp, ConverterError},
+    sources::{currency_api::CurrencyApi, ConverterDataSource, CurrencyList},
     ConvertArgs,
 };
 use spinoff::{spinners, Color, Spinner};
 
-pub async fn convert(args: &ConvertArgs) -> ! {
+pub mod alert;
+pub mod cache;
+pub mod change;
+pub mod history;
+pub mod quota;
+pub mod refresh;
+pub mod repl;
+pub mod serve;
+pub mod tui;
+pub mod watch;
+
+pub async fn convert(args: &ConvertArgs, config: &Config, offline: bool) -> ! {
     let mut spinner = Spinner::new(spinners::Dots, "Loading the cache...", Color::White);
     let mut cache = match Cache::load() {
         Ok(val) => val,
         Err(_) => Cache::new(),
     };
 
-    let base = args.base.to_uppercase();
-    let target = args.target.to_uppercase();
+    let base = config.currency(&args.base);
+    let (target, amount) = match target_and_amount(config, &args.target, args.amount) {
+        Ok(val) => val,
+        Err(err) => {
+            spinner.stop_and_persist(">", "Invalid arguments.");
+            error_and_exit(err)
+        }
+    };
+    let precision = config.precision(args.precision);
+    let cache_duration = config.cache_duration(args.cache_duration);
 
     spinner.update(spinners::Dots, "Getting the data...", Color::White);
-    let converter = match cache.get(&base, &target, args.cache_duration) {
+    if let Some(date) = args.date {
+        let converter =
+            convert_historical(&mut cache, spinner, &base, &target, date, offline).await;
+        print_conversion(&converter, amount, precision);
+        match cache.save() {
+            Ok(_) => std::process::exit(0),
+            Err(err) => error_and_exit(&err),
+        }
+    }
+
+    if offline {
+        let converter = match lookup_offline(&cache, &base, &target, cache_duration, args.stale_by)
+        {
+            Ok(val) => val,
+            Err(err) => {
+                spinner.stop_and_persist(">", "No cached data available.");
+                error_and_exit(err)
+            }
+        };
+        spinner.stop_and_persist(">", "Using cached data (offline mode).");
+        print_conversion(&converter, amount, precision);
+        std::process::exit(0);
+    }
+
+    let converter = match cache.get(&base, &target, cache_duration, args.stale_by) {
         Some(val) => {
             spinner.stop_and_persist(
                 ">",
                 format!(
                     "Using cached data ({}s before considered stale).",
-                    args.cache_duration
+                    cache_duration
                 )
                 .as_str(),
             );
@@ -35,30 +82,40 @@ pub async fn convert(args: &ConvertArgs) -> ! {
                 "Fetching data from the API...",
                 Color::White,
             );
-            let converter = match CurrencyApi::load(&base, &target).await {
-                Ok(val) => val,
+            match CurrencyApi::load(&base, &target).await {
+                Ok(converter) => {
+                    cache.set(&converter);
+                    spinner.stop_and_persist(">", "Data successfully fetched from the API.");
+                    converter
+                }
                 Err(err) => {
-                    spinner.stop_and_persist(">", "Failed to fetch data from the API.");
-                    error_and_exit(err)
+                    let stale = args.allow_stale.and_then(|max_age| {
+                        cache.get_stale(&base, &target, max_age, args.stale_by)
+                    });
+                    match stale {
+                        Some((converter, age)) => {
+                            spinner.stop_and_persist(
+                                ">",
+                                "Failed to fetch data from the API, falling back to stale cached data.",
+                            );
+                            eprintln!(
+                                "Warning: {}\nWarning: using a cached rate that is {} old.",
+                                err,
+                                format_duration(age)
+                            );
+                            converter
+                        }
+                        None => {
+                            spinner.stop_and_persist(">", "Failed to fetch data from the API.");
+                            error_and_exit(err)
+                        }
+                    }
                 }
-            };
-            cache.set(&converter);
-            spinner.stop_and_persist(">", "Data successfully fetched from the API.");
-            converter
+            }
         }
     };
 
-    let result = converter.convert(args.amount);
-
-    println!(
-        "{amount} {base} = {result:.precision$} {target} (1 {base} ~= {rate:.precision$} {target})",
-        amount = args.amount,
-        base = base,
-        result = result,
-        target = target,
-        precision = args.precision,
-        rate = converter.rate
-    );
+    print_conversion(&converter, amount, precision);
 
     match cache.save() {
         Ok(_) => std::process::exit(0),
@@ -66,10 +123,75 @@ pub async fn convert(args: &ConvertArgs) -> ! {
     }
 }
 
-pub async fn list() -> ! {
-    let list = match CurrencyApi::list().await {
-        Ok(val) => val,
-        Err(err) => error_and_exit(err),
+/// Gets the rate at the end of a past date for `convert --date`, exiting if
+/// it is not available
+async fn convert_historical(
+    cache: &mut Cache,
+    mut spinner: Spinner,
+    base: &str,
+    target: &str,
+    date: NaiveDate,
+    offline: bool,
+) -> Converter {
+    if date >= Utc::now().date_naive() {
+        spinner.stop_and_persist(">", "Invalid date.");
+        error_and_exit(format!(
+            "The date {} is not in the past, omit --date to use the latest rate.",
+            date
+        ))
+    }
+
+    match cache.get_historical(base, target, date) {
+        Some(val) => {
+            spinner.stop_and_persist(">", "Using cached historical data.");
+            val
+        }
+        None if offline => {
+            spinner.stop_and_persist(">", "No cached data available.");
+            error_and_exit(ConverterError::Offline(format!(
+                "no cached rate for {}/{} on {}",
+                base, target, date
+            )))
+        }
+        None => {
+            spinner.update(
+                spinners::Dots,
+                "Fetching historical data from the API...",
+                Color::White,
+            );
+            match CurrencyApi::historical(base, target, date).await {
+                Ok(converter) => {
+                    cache.set_historical(&converter, date);
+                    spinner.stop_and_persist(">", "Data successfully fetched from the API.");
+                    converter
+                }
+                Err(err) => {
+                    spinner.stop_and_persist(">", "Failed to fetch data from the API.");
+                    error_and_exit(err)
+                }
+            }
+        }
+    }
+}
+
+pub async fn list(offline: bool) -> ! {
+    let list = if offline {
+        let cache = match Cache::load() {
+            Ok(val) => val,
+            Err(_) => Cache::new(),
+        };
+        let currencies = cache.currencies();
+        if currencies.is_empty() {
+            error_and_exit(ConverterError::Offline(
+                "the cache does not contain any currencies".to_string(),
+            ))
+        }
+        CurrencyList { currencies }
+    } else {
+        match CurrencyApi::list().await {
+            Ok(val) => val,
+            Err(err) => error_and_exit(err),
+        }
     };
 
     println!("{}", list);
@@ -77,104 +199,138 @@ pub async fn list() -> ! {
     std::process::exit(0);
 }
 
-pub async fn interactive() -> ! {
-    println!("Cache duration in seconds (300s): ");
-    let mut cache_duration = String::new();
-    std::io::stdin()
-        .read_line(&mut cache_duration)
-        .expect("Failed to read line");
-    let cache_duration: u64 = cache_duration.trim().parse().unwrap_or(300);
-
-    println!("Precision (2): ");
-    let mut precision = String::new();
-    std::io::stdin()
-        .read_line(&mut precision)
-        .expect("Failed to read line");
-
-    let precision: usize = precision.trim().parse().unwrap_or(2);
-    let mut cache = match Cache::load() {
-        Ok(val) => val,
-        Err(_) => Cache::new(),
-    };
+/// Reads the target currency and amount of `convert`, where the amount may be
+/// given in place of the target to convert into the home currency
+fn target_and_amount(
+    config: &Config,
+    target: &str,
+    amount: Option<f64>,
+) -> Result<(String, f64), String> {
+    if let Some(amount) = amount {
+        return Ok((config.currency(target), amount));
+    }
+    let amount = target
+        .parse()
+        .map_err(|_| format!("Invalid amount `{}`.", target))?;
+    match config.home() {
+        Some(home) => Ok((home, amount)),
+        None => Err(
+            "No target currency was given and no home currency is set, set `home` in the config file."
+                .to_string(),
+        ),
+    }
+}
 
-    loop {
-        println!("Base currency: ");
-        let mut base = String::new();
-        std::io::stdin()
-            .read_line(&mut base)
-            .expect("Failed to read line");
-        base = base.trim().to_string().to_uppercase();
-
-        println!("Target currency: ");
-        let mut target = String::new();
-        std::io::stdin()
-            .read_line(&mut target)
-            .expect("Failed to read line");
-
-        target = target.trim().to_string().to_uppercase();
-
-        println!("Amount: ");
-        let mut amount = String::new();
-        std::io::stdin()
-            .read_line(&mut amount)
-            .expect("Failed to read line");
-
-        let amount: f64 = match amount.trim().parse() {
-            Ok(val) => val,
-            Err(_) => {
-                println!("Invalid amount, please try again.");
-                continue;
-            }
-        };
+/// Gets the current rate from the cache, or from the API once the cached
+/// rate is older than the cache duration
+async fn get_rate(
+    cache: &mut Cache,
+    base: &str,
+    target: &str,
+    cache_duration: u64,
+    offline: bool,
+) -> Result<Converter, ConverterError> {
+    if offline {
+        return lookup_offline(cache, base, target, cache_duration, Staleness::Fetched);
+    }
+    if let Some(converter) = cache.get(base, target, cache_duration, Staleness::Fetched) {
+        return Ok(converter);
+    }
+    let converter = CurrencyApi::load(base, target).await?;
+    cache.set(&converter);
+    Ok(converter)
+}
 
-        let mut spinner = Spinner::new(spinners::Dots, "Getting the data...", Color::White);
-        let converter = match cache.get(&base, &target, cache_duration) {
-            Some(val) => {
-                spinner.stop_and_persist(
-                    "\n>",
-                    format!(
-                        "Using cached data ({}s before considered stale).",
-                        cache_duration
-                    )
-                    .as_str(),
-                );
-                val
-            }
-            None => {
-                spinner.update(
-                    spinners::Dots,
-                    "Fetching data from the API...",
-                    Color::White,
+/// Gets the rate on a past date from the cache, or from the API if it has
+/// not been fetched before
+async fn get_historical_rate(
+    cache: &mut Cache,
+    base: &str,
+    target: &str,
+    date: NaiveDate,
+    offline: bool,
+) -> Result<Converter, ConverterError> {
+    if let Some(converter) = cache.get_historical(base, target, date) {
+        return Ok(converter);
+    }
+    if offline {
+        return Err(ConverterError::Offline(format!(
+            "no cached rate for {}/{} on {}",
+            base, target, date
+        )));
+    }
+    let converter = CurrencyApi::historical(base, target, date).await?;
+    cache.set_historical(&converter, date);
+    Ok(converter)
+}
+
+/// Looks up the newest cached rate regardless of its age, warning if it is
+/// older than the cache duration
+fn lookup_offline(
+    cache: &Cache,
+    base: &str,
+    target: &str,
+    cache_duration: u64,
+    staleness: Staleness,
+) -> Result<Converter, ConverterError> {
+    match cache.get_stale(base, target, None, staleness) {
+        Some((converter, age)) => {
+            if age >= cache_duration {
+                eprintln!(
+                    "Warning: using a cached rate that is {} old.",
+                    format_duration(age)
                 );
-                let converter = match CurrencyApi::load(&base, &target).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        spinner.stop_and_persist("\n>", "Failed to fetch data from the API.");
-                        println!("There was an error while fetching data from the API ({}). Please try again.", err);
-                        continue;
-                    }
-                };
-                cache.set(&converter);
-                spinner.stop_and_persist("\n>", "Data successfully fetched from the API.");
-                converter
             }
-        };
+            Ok(converter)
+        }
+        None => Err(ConverterError::Offline(format!(
+            "no cached rate for {}/{}",
+            base, target
+        ))),
+    }
+}
 
-        let result = converter.convert(amount);
+fn print_conversion(converter: &Converter, amount: f64, precision: usize) {
+    println!(
+        "{amount} {base} = {result:.precision$} {target} (1 {base} ~= {rate:.precision$} {target})",
+        amount = amount,
+        base = converter.base,
+        result = converter.convert(amount),
+        target = converter.target,
+        precision = precision,
+        rate = converter.rate
+    );
+    print_as_of(converter);
+}
 
+fn print_as_of(converter: &Converter) {
+    if let Some(updated_at) = converter.updated_at {
         println!(
-            "{amount} {base} = {result:.precision$} {target} (1 {base} ~= {rate:.precision$} {target})\n",
-            amount = amount,
-            base = base.trim(),
-            result = result,
-            target = target.trim(),
-            precision = precision,
-            rate = converter.rate
+            "Rate as of {} ({}).",
+            format_timestamp(updated_at),
+            converter.source
         );
+    }
+}
 
-        match cache.save() {
-            Ok(_) => (),
-            Err(err) => error_and_exit(&err),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_currency() {
+        let mut config = Config::default();
+        assert_eq!(
+            target_and_amount(&config, "eur", Some(1.0)),
+            Ok(("EUR".to_string(), 1.0))
+        );
+        assert!(target_and_amount(&config, "100", None).is_err());
+
+        config.settings.home = Some("pln".to_string());
+        assert_eq!(
+            target_and_amount(&config, "100", None),
+            Ok(("PLN".to_string(), 100.0))
+        );
+        assert!(target_and_amount(&config, "eur", None).is_err());
     }
 }
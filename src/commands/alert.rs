@@ -0,0 +1,124 @@
+use std::process::Command;
+
+use crate::{
+    alert::{Alert, Condition},
+    cache::Cache,
+    config::Config,
+    converter::Pair,
+    helpers::error_and_exit,
+};
+
+use super::get_rate;
+
+/// The exit status of `alert check` when any alert triggered
+const TRIGGERED: i32 = 2;
+
+fn save_config(config: &Config) -> ! {
+    match config.save() {
+        Ok(_) => std::process::exit(0),
+        Err(err) => error_and_exit(&err),
+    }
+}
+
+pub fn add(mut config: Config, base: &str, target: &str, condition: Condition) -> ! {
+    let pair: Pair = match format!("{}/{}", config.currency(base), config.currency(target)).parse()
+    {
+        Ok(val) => val,
+        Err(err) => error_and_exit(err),
+    };
+    let alert = Alert { pair, condition };
+    if config.alerts.contains(&alert) {
+        error_and_exit(format!("The alert `{}` already exists.", alert))
+    }
+    println!("Added the alert `{}`.", alert);
+    config.alerts.push(alert);
+    save_config(&config)
+}
+
+pub fn list(config: &Config) -> ! {
+    if config.alerts.is_empty() {
+        println!("No alerts are configured.");
+    }
+    for (number, alert) in config.alerts.iter().enumerate() {
+        println!("{:>3}. {}", number + 1, alert);
+    }
+    std::process::exit(0);
+}
+
+pub fn remove(mut config: Config, number: usize) -> ! {
+    if number == 0 || number > config.alerts.len() {
+        error_and_exit(format!(
+            "There is no alert number {}, see `alert list`.",
+            number
+        ))
+    }
+    let alert = config.alerts.remove(number - 1);
+    println!("Removed the alert `{}`.", alert);
+    save_config(&config)
+}
+
+pub async fn check(config: &Config, cache_duration: u64, exec: Option<&str>, offline: bool) -> ! {
+    if config.alerts.is_empty() {
+        println!("No alerts are configured.");
+        std::process::exit(0);
+    }
+
+    let mut cache = match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    };
+
+    let mut triggered = Vec::new();
+    let mut failed = false;
+    for alert in &config.alerts {
+        let Pair { base, target } = &alert.pair;
+        let rate = match get_rate(&mut cache, base, target, cache_duration, offline).await {
+            Ok(val) => val.rate,
+            Err(err) => {
+                eprintln!("Error while checking `{}`: {}", alert, err);
+                failed = true;
+                continue;
+            }
+        };
+        let previous = cache
+            .last_two(base, target)
+            .map(|(previous, _)| previous.rate);
+        if let Some(message) = alert.check(rate, previous) {
+            println!("{}", message);
+            triggered.push(message);
+        }
+    }
+
+    if let Err(err) = cache.save() {
+        eprintln!("Error while saving the cache: {}", err);
+    }
+
+    if !triggered.is_empty() {
+        if let Some(command) = exec.or(config.alert_command.as_deref()) {
+            run(command, &triggered.join("\n"));
+        }
+        std::process::exit(TRIGGERED);
+    }
+    std::process::exit(if failed { 1 } else { 0 });
+}
+
+/// Runs the alert command through the shell, passing the triggered alerts in
+/// the `CURRENCY_ALERTS` environment variable, one per line
+fn run(command: &str, alerts: &str) {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    match shell.arg(command).env("CURRENCY_ALERTS", alerts).status() {
+        Ok(status) if !status.success() => {
+            eprintln!("Warning: the alert command exited with {}", status)
+        }
+        Ok(_) => {}
+        Err(err) => eprintln!("Warning: could not run the alert command: {}", err),
+    }
+}
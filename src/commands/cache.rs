@@ -0,0 +1,171 @@
+use std::{fs, path::Path};
+
+use crate::{
+    cache::{
+        snapshot::{MergeStrategy, Snapshot, SnapshotFormat},
+        Cache,
+    },
+    helpers::{error_and_exit, format_duration, format_timestamp, now},
+};
+
+fn load() -> Cache {
+    match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    }
+}
+
+fn save(cache: &Cache) -> ! {
+    match cache.save() {
+        Ok(_) => std::process::exit(0),
+        Err(err) => error_and_exit(&err),
+    }
+}
+
+pub fn show() -> ! {
+    let cache = load();
+    let rates = cache.rates();
+    if rates.is_empty() {
+        println!("The cache is empty.");
+        std::process::exit(0);
+    }
+
+    println!(
+        "{:<9} {:>14} {:>9} {:<14} As of",
+        "Pair", "Rate", "Age", "Source"
+    );
+    for rate in rates {
+        println!(
+            "{:<9} {:>14.6} {:>9} {:<14} {}",
+            format!("{}/{}", rate.base, rate.target),
+            rate.rate,
+            format_duration(now().saturating_sub(rate.fetched_at)),
+            rate.source,
+            rate.updated_at.map(format_timestamp).unwrap_or_default()
+        );
+    }
+
+    std::process::exit(0);
+}
+
+pub fn clear(base: Option<&str>, target: Option<&str>) -> ! {
+    let mut cache = load();
+    let base = base.map(|x| x.to_uppercase());
+    let target = target.map(|x| x.to_uppercase());
+    let removed = cache.clear(base.as_deref(), target.as_deref());
+    println!("Removed {} cached rate(s).", removed);
+    save(&cache)
+}
+
+pub fn prune(max_age: u64) -> ! {
+    let mut cache = load();
+    let removed = cache.prune(max_age);
+    println!(
+        "Removed {} cached rate(s) older than {}.",
+        removed,
+        format_duration(max_age)
+    );
+    save(&cache)
+}
+
+pub fn stats() -> ! {
+    let cache = load();
+    let path = match Cache::path() {
+        Ok(val) => val,
+        Err(err) => error_and_exit(&err),
+    };
+    let rates = cache.rates();
+    let stats = cache.stats();
+    let lookups = stats.hits + stats.misses;
+
+    match fs::metadata(&path) {
+        Ok(metadata) => println!("Cache file: {} ({} bytes)", path.display(), metadata.len()),
+        Err(_) => println!("Cache file: {} (not created yet)", path.display()),
+    }
+    println!(
+        "Cached rates: {} across {} base currencies",
+        rates.len(),
+        rates
+            .iter()
+            .map(|x| &x.base)
+            .collect::<std::collections::HashSet<_>>()
+            .len()
+    );
+    println!("Observations: {}", cache.observation_count());
+    println!("Historical rates: {}", cache.historical_count());
+    if lookups > 0 {
+        println!(
+            "Lookups: {} hits, {} misses ({:.1}% hit rate)",
+            stats.hits,
+            stats.misses,
+            stats.hits as f64 / lookups as f64 * 100.0
+        );
+    } else {
+        println!("Lookups: none recorded yet");
+    }
+
+    std::process::exit(0);
+}
+
+pub fn path() -> ! {
+    match Cache::path() {
+        Ok(val) => println!("{}", val.display()),
+        Err(err) => error_and_exit(&err),
+    }
+
+    std::process::exit(0);
+}
+
+pub fn export(output: Option<&Path>, format: Option<SnapshotFormat>) -> ! {
+    let cache = load();
+    let format = format.unwrap_or_else(|| match output {
+        Some(output) => SnapshotFormat::from_path(output),
+        None => SnapshotFormat::Json,
+    });
+    let contents = match cache.snapshot().render(format) {
+        Ok(val) => val,
+        Err(err) => error_and_exit(&err),
+    };
+
+    match output {
+        Some(output) => {
+            if let Err(err) = fs::write(output, contents) {
+                error_and_exit(&err)
+            }
+            eprintln!(
+                "Exported {} cached rate(s) to {}.",
+                cache.rates().len(),
+                output.display()
+            );
+        }
+        None => print!("{}", contents),
+    }
+
+    std::process::exit(0);
+}
+
+pub fn import(file: &Path, format: Option<SnapshotFormat>, merge: MergeStrategy) -> ! {
+    let mut cache = load();
+    let format = format.unwrap_or_else(|| SnapshotFormat::from_path(file));
+    let contents = match fs::read_to_string(file) {
+        Ok(val) => val,
+        Err(err) => error_and_exit(&err),
+    };
+    let snapshot = match Snapshot::parse(&contents, format) {
+        Ok(val) => val,
+        Err(err) => error_and_exit(format!(
+            "Error while reading the snapshot {}: {}",
+            file.display(),
+            err
+        )),
+    };
+
+    let imported = cache.import(&snapshot, merge);
+    println!(
+        "Imported {} of {} rate(s) from {}.",
+        imported,
+        snapshot.rates.len(),
+        file.display()
+    );
+    save(&cache)
+}
@@ -0,0 +1,105 @@
+use chrono::Utc;
+
+use crate::{
+    cache::Cache,
+    config::Config,
+    helpers::{error_and_exit, format_timestamp},
+    series::RateChange,
+    ChangeArgs,
+};
+
+use super::{get_historical_rate, get_rate};
+
+pub async fn change(args: &ChangeArgs, config: &Config, offline: bool) -> ! {
+    let base = config.currency(&args.base);
+    let target = config.currency(&args.target);
+    let precision = config.precision(args.precision);
+    let cache_duration = config.cache_duration(args.cache_duration);
+    let today = Utc::now().date_naive();
+    for date in [args.from, args.to].into_iter().flatten() {
+        if date >= today {
+            error_and_exit(format!("The date {} is not in the past.", date))
+        }
+    }
+    if let (Some(from), Some(to)) = (args.from, args.to) {
+        if from >= to {
+            error_and_exit(format!(
+                "The start date {} is not before the end date {}.",
+                from, to
+            ))
+        }
+    }
+
+    let mut cache = match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    };
+
+    let ((start, start_label), (end, end_label)) = match args.from {
+        Some(from) => {
+            let start = match get_historical_rate(&mut cache, &base, &target, from, offline).await {
+                Ok(val) => val.rate,
+                Err(err) => error_and_exit(err),
+            };
+            let end = match args.to {
+                Some(to) => get_historical_rate(&mut cache, &base, &target, to, offline)
+                    .await
+                    .map(|x| (x.rate, format!("on {}", to))),
+                None => get_rate(&mut cache, &base, &target, cache_duration, offline)
+                    .await
+                    .map(|x| (x.rate, "now".to_string())),
+            };
+            match end {
+                Ok(val) => ((start, format!("on {}", from)), val),
+                Err(err) => error_and_exit(err),
+            }
+        }
+        None => {
+            if let Err(err) = get_rate(&mut cache, &base, &target, cache_duration, offline).await {
+                error_and_exit(err)
+            }
+            match cache.last_two(&base, &target) {
+                Some((previous, current)) => (
+                    (
+                        previous.rate,
+                        format!(
+                            "as of {}",
+                            format_timestamp(previous.updated_at.unwrap_or(previous.fetched_at))
+                        ),
+                    ),
+                    (current.rate, "now".to_string()),
+                ),
+                _ => error_and_exit(format!(
+                    "No earlier rate for {}/{} is stored yet, use --from to compare with a past date.",
+                    base, target
+                )),
+            }
+        }
+    };
+
+    let change = RateChange::new(start, end);
+    println!(
+        "{base}/{target}: {start:.precision$} {start_label} -> {end:.precision$} {end_label}",
+        precision = precision + 2
+    );
+    println!(
+        "Change: {absolute:+.precision$} ({percent:+.2}%)",
+        absolute = change.absolute,
+        percent = change.percent,
+        precision = precision + 2
+    );
+    if let Some(amount) = args.amount {
+        println!(
+            "{amount} {base}: {from:.precision$} {target} -> {to:.precision$} {target} ({difference:+.precision$} {target})",
+            from = amount * start,
+            to = amount * end,
+            difference = amount * change.absolute,
+            precision = precision
+        );
+    }
+
+    match cache.save() {
+        Ok(_) => std::process::exit(0),
+        Err(err) => error_and_exit(&err),
+    }
+}
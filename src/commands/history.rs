@@ -0,0 +1,87 @@
+use chrono::{Days, NaiveDate, Utc};
+
+use crate::{
+    cache::Cache,
+    config::Config,
+    helpers::error_and_exit,
+    series::Series,
+    sources::{currency_api::CurrencyApi, ConverterDataSource},
+    HistoryArgs,
+};
+
+pub async fn history(args: &HistoryArgs, config: &Config, offline: bool) -> ! {
+    let base = config.currency(&args.base);
+    let target = config.currency(&args.target);
+    let today = Utc::now().date_naive();
+    let yesterday = today - Days::new(1);
+    let from = args.from;
+    let to = args.to.unwrap_or(yesterday);
+    if from > to {
+        error_and_exit(format!(
+            "The start date {} is after the end date {}.",
+            from, to
+        ))
+    }
+    if to > today {
+        error_and_exit(format!("The end date {} is in the future.", to))
+    }
+
+    let mut cache = match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    };
+    let mut rates = cache.daily_rates(&base, &target, from, to);
+
+    // Only past days can be fetched, today's rate comes from the observations
+    let missing: Vec<NaiveDate> = from
+        .iter_days()
+        .take_while(|date| *date <= to.min(yesterday))
+        .filter(|date| !rates.contains_key(date))
+        .collect();
+    if let (Some(first), Some(last)) = (missing.first(), missing.last()) {
+        if offline || args.local {
+            eprintln!(
+                "Warning: {} day(s) between {} and {} are not stored locally.",
+                missing.len(),
+                first,
+                last
+            );
+        } else {
+            eprintln!(
+                "Fetching daily rates from {} to {} from the API...",
+                first, last
+            );
+            match CurrencyApi::timeseries(&base, &target, *first, *last).await {
+                Ok(converters) => {
+                    for (date, converter) in converters {
+                        cache.set_historical(&converter, date);
+                        rates.entry(date).or_insert(converter.rate);
+                    }
+                }
+                Err(err) if rates.is_empty() => error_and_exit(err),
+                Err(err) => eprintln!(
+                    "Warning: {}\nWarning: only showing locally stored rates.",
+                    err
+                ),
+            }
+        }
+    }
+
+    if rates.is_empty() {
+        error_and_exit(format!(
+            "No rates are available for {}/{} between {} and {}.",
+            base, target, from, to
+        ))
+    }
+
+    let series = Series::new(base, target, rates);
+    print!(
+        "{}",
+        series.render(config.format(args.format), args.precision)
+    );
+
+    match cache.save() {
+        Ok(_) => std::process::exit(0),
+        Err(err) => error_and_exit(&err),
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{
+    config::Config,
+    quota::{current_month, Quota},
+    sources::SOURCES,
+};
+
+pub fn quota(config: &Config) -> ! {
+    let quota = Quota::load().unwrap_or_default();
+    let limits = config.quota();
+    let month = current_month();
+
+    println!("API requests in {}:", month);
+    for source in SOURCES {
+        let used = quota.used(source, &month);
+        match limits.budget {
+            Some(budget) => println!(
+                "  {}: {} of {} ({:.0}%), {} left",
+                source,
+                used,
+                budget,
+                used as f64 / budget.max(1) as f64 * 100.0,
+                budget.saturating_sub(used)
+            ),
+            None => println!("  {}: {}", source, used),
+        }
+    }
+    match limits.budget {
+        Some(_) if limits.enforce => {
+            println!("Requests are refused once the quota is used up, using cached rates instead.")
+        }
+        Some(_) => {}
+        None => println!("No quota is set, set `quota` in the config file to track a budget."),
+    }
+
+    std::process::exit(0);
+}
@@ -0,0 +1,142 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    cache::{Cache, Staleness},
+    config::Config,
+    converter::Pair,
+    helpers::{error_and_exit, format_timestamp, now, ConverterError},
+    sources::{currency_api::CurrencyApi, ConverterDataSource},
+    RefreshArgs,
+};
+
+pub async fn refresh(args: &RefreshArgs, config: &Config, offline: bool) -> ! {
+    if offline {
+        error_and_exit(ConverterError::Offline(
+            "refreshing the watchlist requires the API".to_string(),
+        ))
+    }
+
+    if config.watchlist.is_empty() {
+        error_and_exit(format!(
+            "The watchlist is empty, add pairs to it in {}, e.g. watchlist = [\"USD/EUR\"]",
+            config.path.display()
+        ))
+    }
+    let cache_duration = config.cache_duration(args.cache_duration);
+    // Counted across every cycle, so the budget covers the whole run
+    let mut requests = 0;
+
+    loop {
+        // Reloaded on every cycle so conversions made in the meantime are kept
+        let mut cache = match Cache::load() {
+            Ok(val) => val,
+            Err(_) => Cache::new(),
+        };
+        refresh_pairs(
+            &mut cache,
+            &config.watchlist,
+            cache_duration,
+            args.max_requests,
+            &mut requests,
+        )
+        .await;
+        if let Err(err) = cache.save() {
+            if args.interval.is_none() {
+                error_and_exit(&err)
+            }
+            // The rates are saved again with the next cycle
+            log(&format!("Failed to save the cache: {}", err));
+        }
+
+        match args.interval {
+            Some(interval) => tokio::time::sleep(Duration::from_secs(interval)).await,
+            None => std::process::exit(0),
+        }
+    }
+}
+
+async fn refresh_pairs(
+    cache: &mut Cache,
+    pairs: &[Pair],
+    cache_duration: u64,
+    max_requests: Option<u32>,
+    requests: &mut u32,
+) {
+    let stale: Vec<&Pair> = pairs
+        .iter()
+        .filter(|pair| {
+            !cache.is_fresh(&pair.base, &pair.target, cache_duration, Staleness::Fetched)
+        })
+        .collect();
+    if stale.is_empty() {
+        log("All pairs in the watchlist are fresh.");
+        return;
+    }
+
+    for (base, targets) in group_by_base(&stale) {
+        if let Some(max_requests) = max_requests {
+            if *requests >= max_requests {
+                log(&format!(
+                    "Skipping {} -> {} (the budget of {} request(s) was reached).",
+                    base,
+                    targets.join(", "),
+                    max_requests
+                ));
+                continue;
+            }
+        }
+        *requests += 1;
+
+        match CurrencyApi::load_many(&base, &targets).await {
+            Ok(converters) => {
+                for converter in &converters {
+                    cache.set(converter);
+                }
+                log(&format!("Refreshed {} -> {}.", base, targets.join(", ")));
+            }
+            // No other request can be made, the cached rates are kept
+            Err(ConverterError::QuotaExceeded(err)) => {
+                log(&format!(
+                    "Stopped refreshing, keeping the cached rates: {}",
+                    err
+                ));
+                return;
+            }
+            Err(err) => log(&format!("Failed to refresh {}: {}", base, err)),
+        }
+    }
+}
+
+/// Groups the pairs by base currency, so each base needs a single request
+fn group_by_base(pairs: &[&Pair]) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for pair in pairs {
+        let targets = groups.entry(pair.base.clone()).or_default();
+        if !targets.contains(&pair.target) {
+            targets.push(pair.target.clone());
+        }
+    }
+    groups
+}
+
+fn log(message: &str) {
+    println!("[{}] {}", format_timestamp(now()), message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_pairs_by_base() {
+        let pairs: Vec<Pair> = ["USD/EUR", "EUR/PLN", "USD/PLN", "USD/EUR"]
+            .iter()
+            .map(|x| x.parse().unwrap())
+            .collect();
+        let pairs: Vec<&Pair> = pairs.iter().collect();
+        let groups = group_by_base(&pairs);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["EUR"], vec!["PLN"]);
+        assert_eq!(groups["USD"], vec!["EUR", "PLN"]);
+    }
+}
@@ -0,0 +1,479 @@
+use std::path::PathBuf;
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+use spinoff::{spinners, Color, Spinner};
+
+use crate::{
+    cache::{Cache, Staleness},
+    config::Config,
+    converter::{Converter, Pair},
+    helpers::{error_and_exit, ConverterError},
+    sources::{self, currency_api::CurrencyApi, ConverterDataSource, SOURCES},
+    InteractiveArgs,
+};
+
+use super::{lookup_offline, print_conversion};
+
+const COMMANDS: [&str; 6] = [":help", ":precision", ":quit", ":r", ":source", ":swap"];
+
+const HELP: &str = "Enter an amount and two currencies to convert, e.g. `100 USD EUR`.
+The pair is remembered, so `200` converts another amount and `50 GBP` only
+changes the base currency (a base equal to the current target reverses the pair).
+Without a pair, `50 GBP` converts into the home currency if one is configured.
+Commands:
+  :swap, :r         reverse the pair and convert the last amount again
+  :precision [N]    show or set the precision of the results
+  :source [NAME]    show or set the source of the rates
+  :help             show this help
+  :quit             quit, as does Ctrl-D";
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    Empty,
+    /// Currencies left out are taken from the current pair
+    Convert {
+        amount: f64,
+        base: Option<String>,
+        target: Option<String>,
+    },
+    Swap,
+    Precision(Option<usize>),
+    Source(Option<&'static str>),
+    Help,
+    Quit,
+}
+
+impl Input {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        // Allow the same form as on the command line, e.g. `convert 100`
+        if words.first() == Some(&"convert") {
+            words.remove(0);
+        }
+        match words.as_slice() {
+            [] => Ok(Input::Empty),
+            [":swap"] | [":r"] => Ok(Input::Swap),
+            [":precision"] => Ok(Input::Precision(None)),
+            [":precision", precision] => match precision.parse() {
+                Ok(val) => Ok(Input::Precision(Some(val))),
+                Err(_) => Err(format!("Invalid precision `{}`.", precision)),
+            },
+            [":source"] => Ok(Input::Source(None)),
+            [":source", source] => match SOURCES.iter().find(|x| x.eq_ignore_ascii_case(source)) {
+                Some(source) => Ok(Input::Source(Some(source))),
+                None => Err(format!(
+                    "Unknown source `{}`, available sources: {}.",
+                    source,
+                    SOURCES.join(", ")
+                )),
+            },
+            [":help"] => Ok(Input::Help),
+            [":quit"] | [":q"] => Ok(Input::Quit),
+            [command, ..] if command.starts_with(':') => {
+                Err(format!("Unknown command `{}`, see `:help`.", line.trim()))
+            }
+            [amount, currencies @ ..] if currencies.len() <= 2 => match amount.parse() {
+                Ok(amount) => Ok(Input::Convert {
+                    amount,
+                    base: currencies.first().map(|x| x.to_uppercase()),
+                    target: currencies.get(1).map(|x| x.to_uppercase()),
+                }),
+                Err(_) => Err(format!("Invalid amount `{}`.", amount)),
+            },
+            _ => {
+                Err("Expected an amount and up to two currencies, e.g. `100 USD EUR`.".to_string())
+            }
+        }
+    }
+}
+
+/// Completes currency codes and commands
+struct ReplHelper {
+    currencies: Vec<String>,
+}
+
+impl ReplHelper {
+    fn candidates(&self, word: &str) -> Vec<String> {
+        if word.starts_with(':') {
+            return COMMANDS
+                .iter()
+                .filter(|x| x.starts_with(word))
+                .map(|x| x.to_string())
+                .collect();
+        }
+        let word = word.to_uppercase();
+        self.currencies
+            .iter()
+            .filter(|x| x.starts_with(&word))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = word_start(line, pos);
+        let word = &line[start..pos];
+        if word.is_empty() || word.starts_with(|x: char| x.is_ascii_digit()) {
+            return Ok((start, Vec::new()));
+        }
+        Ok((start, self.candidates(word)))
+    }
+}
+
+/// The byte index of the word ending at the cursor, after the whitespace
+/// before it, which may take several bytes
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .find(|(_, x)| x.is_whitespace())
+        .map_or(0, |(index, x)| index + x.len_utf8())
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+struct Session {
+    cache: Cache,
+    cache_duration: u64,
+    precision: usize,
+    source: &'static str,
+    offline: bool,
+    /// The pair and amount of the last conversion
+    pair: Option<Pair>,
+    amount: Option<f64>,
+}
+
+impl Session {
+    async fn convert(&mut self, amount: f64, pair: Pair) {
+        match self.get_rate(&pair.base, &pair.target).await {
+            Ok(converter) => {
+                print_conversion(&converter, amount, self.precision);
+                if let Err(err) = self.cache.save() {
+                    error_and_exit(&err)
+                }
+            }
+            Err(err) => println!("{}.", err),
+        }
+        self.pair = Some(pair);
+        self.amount = Some(amount);
+    }
+
+    async fn get_rate(&mut self, base: &str, target: &str) -> Result<Converter, ConverterError> {
+        let mut spinner = Spinner::new(spinners::Dots, "Getting the data...", Color::White);
+        if self.offline {
+            let result = lookup_offline(
+                &self.cache,
+                base,
+                target,
+                self.cache_duration,
+                Staleness::Fetched,
+            );
+            match result {
+                Ok(_) => spinner.stop_and_persist(">", "Using cached data (offline mode)."),
+                Err(_) => spinner.stop_and_persist(">", "No cached data available."),
+            }
+            return result;
+        }
+
+        if let Some(converter) =
+            self.cache
+                .get(base, target, self.cache_duration, Staleness::Fetched)
+        {
+            spinner.stop_and_persist(
+                ">",
+                format!(
+                    "Using cached data ({}s before considered stale).",
+                    self.cache_duration
+                )
+                .as_str(),
+            );
+            return Ok(converter);
+        }
+
+        spinner.update(
+            spinners::Dots,
+            format!("Fetching data from {}...", self.source),
+            Color::White,
+        );
+        match sources::load(self.source, base, target).await {
+            Ok(converter) => {
+                spinner.stop_and_persist(">", "Data successfully fetched from the API.");
+                self.cache.set(&converter);
+                Ok(converter)
+            }
+            Err(err) => {
+                spinner.stop_and_persist(">", "Failed to fetch data from the API.");
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Fills in the currencies left out of a conversion from the current pair, or
+/// the home currency before there is one
+fn next_pair(
+    current: Option<&Pair>,
+    home: Option<String>,
+    base: Option<String>,
+    target: Option<String>,
+) -> Result<Pair, String> {
+    match (current, base, target.or(home.filter(|_| current.is_none()))) {
+        (_, Some(base), Some(target)) => Ok(Pair { base, target }),
+        (Some(current), Some(base), None) if base == current.target => Ok(Pair {
+            base,
+            target: current.base.clone(),
+        }),
+        (Some(current), Some(base), None) => Ok(Pair {
+            base,
+            target: current.target.clone(),
+        }),
+        (Some(current), None, _) => Ok(current.clone()),
+        (None, _, _) => Err("Enter both currencies first, e.g. `100 USD EUR`.".to_string()),
+    }
+}
+
+/// Where the lines entered in interactive mode are kept between sessions
+fn history_path() -> Option<PathBuf> {
+    Some(
+        dirs::data_dir()?
+            .join("currency-converter")
+            .join("history.txt"),
+    )
+}
+
+pub async fn repl(args: &InteractiveArgs, config: &Config, offline: bool) -> ! {
+    let cache = match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    };
+    let currencies = if offline {
+        cache.currencies()
+    } else {
+        match CurrencyApi::list().await {
+            Ok(val) => val.currencies,
+            Err(_) => cache.currencies(),
+        }
+    };
+
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(val) => val,
+        Err(err) => error_and_exit(err),
+    };
+    editor.set_helper(Some(ReplHelper { currencies }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // The history file does not exist on the first run
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session {
+        cache,
+        cache_duration: config.cache_duration(args.cache_duration),
+        precision: config.precision(args.precision),
+        source: SOURCES
+            .into_iter()
+            .find(|x| *x == config.source())
+            .unwrap_or(SOURCES[0]),
+        offline,
+        pair: None,
+        amount: None,
+    };
+    println!("{}\n", HELP);
+
+    loop {
+        let prompt = match &session.pair {
+            Some(pair) => format!("{}> ", pair),
+            None => "> ".to_string(),
+        };
+        let line = match editor.readline(&prompt) {
+            Ok(val) => val,
+            // Ctrl-C only discards the current line
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => error_and_exit(err),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        let input = match Input::parse(&line) {
+            Ok(val) => val,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        };
+        match input {
+            Input::Empty => {}
+            Input::Convert {
+                amount,
+                base,
+                target,
+            } => match next_pair(
+                session.pair.as_ref(),
+                config.home(),
+                base.map(|x| config.currency(&x)),
+                target.map(|x| config.currency(&x)),
+            ) {
+                Ok(pair) => session.convert(amount, pair).await,
+                Err(err) => println!("{}", err),
+            },
+            Input::Swap => match (session.pair.take(), session.amount) {
+                (Some(pair), Some(amount)) => {
+                    let pair = Pair {
+                        base: pair.target,
+                        target: pair.base,
+                    };
+                    session.convert(amount, pair).await
+                }
+                _ => println!("Nothing to swap yet."),
+            },
+            Input::Precision(Some(precision)) => {
+                session.precision = precision;
+                println!("The precision is now {}.", precision);
+            }
+            Input::Precision(None) => println!("The precision is {}.", session.precision),
+            Input::Source(Some(source)) => {
+                session.source = source;
+                println!("Using {} for new rates.", source);
+            }
+            Input::Source(None) => println!(
+                "Using {} for new rates, available sources: {}.",
+                session.source,
+                SOURCES.join(", ")
+            ),
+            Input::Help => println!("{}", HELP),
+            Input::Quit => break,
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(err) = editor.save_history(path) {
+            eprintln!("Warning: could not save the history: {}", err);
+        }
+    }
+    std::process::exit(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_input() {
+        assert_eq!(Input::parse("  "), Ok(Input::Empty));
+        assert_eq!(
+            Input::parse("100 usd eur"),
+            Ok(Input::Convert {
+                amount: 100.0,
+                base: Some("USD".to_string()),
+                target: Some("EUR".to_string())
+            })
+        );
+        assert_eq!(
+            Input::parse("2.5"),
+            Ok(Input::Convert {
+                amount: 2.5,
+                base: None,
+                target: None
+            })
+        );
+        assert_eq!(Input::parse(":r"), Ok(Input::Swap));
+        assert_eq!(Input::parse(":precision 4"), Ok(Input::Precision(Some(4))));
+        assert_eq!(
+            Input::parse(":source CurrencyAPI"),
+            Ok(Input::Source(Some(SOURCES[0])))
+        );
+        assert!(Input::parse(":source ecb").is_err());
+        assert_eq!(Input::parse(":q"), Ok(Input::Quit));
+        assert!(Input::parse(":precision x").is_err());
+        assert!(Input::parse(":unknown").is_err());
+        assert!(Input::parse("abc usd eur").is_err());
+        assert!(Input::parse("100 usd eur pln").is_err());
+    }
+
+    #[test]
+    fn sticky_pair() {
+        let usd_eur: Pair = "USD/EUR".parse().unwrap();
+        let code = |x: &str| Some(x.to_string());
+        assert!(next_pair(None, None, code("USD"), None).is_err());
+        assert_eq!(
+            next_pair(None, None, code("USD"), code("EUR")),
+            Ok(usd_eur.clone())
+        );
+        assert_eq!(
+            next_pair(Some(&usd_eur), None, None, None),
+            Ok(usd_eur.clone())
+        );
+        assert_eq!(
+            next_pair(Some(&usd_eur), None, code("GBP"), None),
+            Ok("GBP/EUR".parse().unwrap())
+        );
+        assert_eq!(
+            next_pair(Some(&usd_eur), None, code("EUR"), None),
+            Ok("EUR/USD".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn home_currency() {
+        let usd_eur: Pair = "USD/EUR".parse().unwrap();
+        let code = |x: &str| Some(x.to_string());
+        assert_eq!(
+            next_pair(None, code("PLN"), code("USD"), None),
+            Ok("USD/PLN".parse().unwrap())
+        );
+        assert!(next_pair(None, code("PLN"), None, None).is_err());
+        // The current pair takes precedence over the home currency
+        assert_eq!(
+            next_pair(Some(&usd_eur), code("PLN"), code("GBP"), None),
+            Ok("GBP/EUR".parse().unwrap())
+        );
+        assert_eq!(
+            Input::parse("convert 100"),
+            Ok(Input::Convert {
+                amount: 100.0,
+                base: None,
+                target: None
+            })
+        );
+    }
+
+    #[test]
+    fn complete_words() {
+        let helper = ReplHelper {
+            currencies: vec!["EUR".to_string(), "PLN".to_string(), "USD".to_string()],
+        };
+        assert_eq!(helper.candidates("u"), vec!["USD"]);
+        assert_eq!(helper.candidates("X"), Vec::<String>::new());
+        assert_eq!(helper.candidates(":s"), vec![":source", ":swap"]);
+        assert_eq!(helper.candidates(":r"), vec![":r"]);
+
+        assert_eq!(word_start("100 us", 6), 4);
+        assert_eq!(word_start("us", 2), 0);
+        let line = "100\u{a0}us";
+        assert_eq!(&line[word_start(line, line.len())..], "us");
+    }
+}
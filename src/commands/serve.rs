@@ -0,0 +1,752 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        MatchedPath, Path, Query, Request, State,
+    },
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{Cache, Staleness},
+    config::Config,
+    converter::Pair,
+    helpers::{error_and_exit, now, ConverterError},
+    metrics,
+    sources::{currency_api::CurrencyApi, ConverterDataSource},
+    ServeArgs,
+};
+
+mod lru;
+
+use lru::Lru;
+
+/// The state shared by every request, fetching the rates from the source `S`
+struct Server<S = CurrencyApi> {
+    config: Config,
+    cache_duration: u64,
+    offline: bool,
+    /// The persistent cache, written to disk periodically
+    cache: Mutex<Cache>,
+    /// Whether the cache has rates that were not written to disk yet
+    dirty: AtomicBool,
+    /// Held while writing the cache to disk, so writes do not overlap
+    flushing: tokio::sync::Mutex<()>,
+    /// The most recently used rates, looked up before the persistent cache
+    memory: Mutex<Lru<Pair, Rate>>,
+    /// Held while fetching rates for a base currency, so concurrent requests
+    /// missing the same rates wait for one fetch instead of making their own
+    fetches: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// The supported currencies, fetched once
+    currencies: Mutex<Option<Vec<String>>>,
+    /// When the rates for every currency were last fetched, per base currency
+    full_fetches: Mutex<HashMap<String, u64>>,
+    source: PhantomData<fn() -> S>,
+}
+
+/// A rate with its timestamps and source
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Rate {
+    rate: f64,
+    source: String,
+    /// When the source last updated the rate, if it reports it
+    updated_at: Option<u64>,
+    /// When the rate was fetched from the source
+    fetched_at: u64,
+}
+
+#[derive(Deserialize)]
+struct ConvertQuery {
+    from: String,
+    to: String,
+    #[serde(default = "one")]
+    amount: f64,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Conversion {
+    from: String,
+    to: String,
+    amount: f64,
+    result: f64,
+    #[serde(flatten)]
+    rate: Rate,
+}
+
+#[derive(Deserialize)]
+struct RatesQuery {
+    /// Comma-separated target currencies, every supported currency if omitted
+    symbols: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Rates {
+    base: String,
+    rates: BTreeMap<String, Rate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Currencies {
+    currencies: Vec<String>,
+}
+
+/// An error returned to the client as `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl From<ConverterError> for ApiError {
+    fn from(err: ConverterError) -> Self {
+        let status = match err {
+            ConverterError::Offline(_) => StatusCode::NOT_FOUND,
+            ConverterError::QuotaExceeded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ConverterError::FetchError(_) | ConverterError::ParseError(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+        };
+        ApiError(status, err.to_string())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError(rejection.status(), rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.1 }));
+        (self.0, body).into_response()
+    }
+}
+
+pub async fn serve(args: &ServeArgs, config: Config, offline: bool) -> ! {
+    metrics::install();
+    let cache = match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    };
+    let cache_duration = config.cache_duration(args.cache_duration);
+    let server = Arc::new(Server::new(
+        config,
+        cache,
+        cache_duration,
+        args.memory_entries,
+        offline,
+    ));
+
+    let listener = match tokio::net::TcpListener::bind(args.listen).await {
+        Ok(val) => val,
+        Err(err) => error_and_exit(format!("Could not listen on {}: {}", args.listen, err)),
+    };
+    println!("Listening on http://{}", args.listen);
+
+    let flusher = server.clone();
+    let flush_interval = Duration::from_secs(args.flush_interval);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(flush_interval);
+        loop {
+            interval.tick().await;
+            flusher.flush().await;
+        }
+    });
+
+    let result = axum::serve(listener, router(server.clone()))
+        .with_graceful_shutdown(shutdown())
+        .await;
+    // Keep the rates fetched since the last flush
+    server.flush().await;
+    match result {
+        Ok(_) => std::process::exit(0),
+        Err(err) => error_and_exit(err),
+    }
+}
+
+/// Waits for Ctrl+C, or for SIGTERM as sent by service managers and container
+/// runtimes
+async fn shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(val) => val,
+            Err(err) => error_and_exit(format!("Could not listen for SIGTERM: {}", err)),
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+fn router(server: Arc<Server>) -> Router {
+    Router::new()
+        .route("/convert", get(convert))
+        .route("/rates/:base", get(rates))
+        .route("/currencies", get(currencies))
+        .route("/metrics", get(render_metrics))
+        .route_layer(middleware::from_fn(track))
+        .with_state(server)
+}
+
+/// Records the duration of every request to a known route
+async fn track(request: Request, next: Next) -> Response {
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics::http_request(path, response.status().as_u16(), started);
+    response
+}
+
+async fn convert(
+    State(server): State<Arc<Server>>,
+    query: Result<Query<ConvertQuery>, QueryRejection>,
+) -> Result<Json<Conversion>, ApiError> {
+    let Query(query) = query?;
+    let pair = server.pair(&query.from, &query.to)?;
+    let mut rates = server
+        .rates(&pair.base, std::slice::from_ref(&pair.target))
+        .await?;
+    let rate = rates.remove(&pair.target).ok_or_else(|| {
+        ApiError::from(ConverterError::Offline(format!(
+            "no cached rate for {}",
+            pair
+        )))
+    })?;
+    metrics::conversion();
+    Ok(Json(Conversion {
+        from: pair.base,
+        to: pair.target,
+        amount: query.amount,
+        result: query.amount * rate.rate,
+        rate,
+    }))
+}
+
+async fn rates(
+    State(server): State<Arc<Server>>,
+    base: Result<Path<String>, PathRejection>,
+    query: Result<Query<RatesQuery>, QueryRejection>,
+) -> Result<Json<Rates>, ApiError> {
+    let (Path(base), Query(query)) = (base?, query?);
+    let base = server.currency(&base)?;
+    // Without symbols every supported currency is fetched, at most once per
+    // cache duration, and the cached rates are returned in between
+    let mut full_fetch = query.symbols.is_none() && server.needs_full_fetch(&base);
+    let targets = match &query.symbols {
+        Some(symbols) => {
+            let mut targets = Vec::new();
+            for symbol in symbols.split(',').filter(|x| !x.trim().is_empty()) {
+                targets.push(server.pair(&base, symbol.trim())?.target);
+            }
+            targets
+        }
+        None if full_fetch => match server.currencies().await {
+            Ok(currencies) => currencies.into_iter().filter(|x| *x != base).collect(),
+            // The cached rates are returned once the quota is used up
+            Err(ConverterError::QuotaExceeded(_)) => {
+                full_fetch = false;
+                server.cached_targets(&base)
+            }
+            Err(err) => return Err(err.into()),
+        },
+        None => server.cached_targets(&base),
+    };
+    let rates = server.rates(&base, &targets).await?;
+    if full_fetch {
+        server
+            .full_fetches
+            .lock()
+            .unwrap()
+            .insert(base.clone(), now());
+    }
+    if rates.is_empty() {
+        return Err(ConverterError::Offline(format!("no cached rates for {}", base)).into());
+    }
+    Ok(Json(Rates { base, rates }))
+}
+
+async fn render_metrics(State(server): State<Arc<Server>>) -> String {
+    {
+        let cache = server.cache.lock().unwrap();
+        for pair in &server.config.watchlist {
+            if let Some((converter, _)) =
+                cache.get_stale(&pair.base, &pair.target, None, Staleness::Fetched)
+            {
+                metrics::rate(pair, converter.rate);
+            }
+        }
+    }
+    metrics::render()
+}
+
+async fn currencies(State(server): State<Arc<Server>>) -> Result<Json<Currencies>, ApiError> {
+    let currencies = if server.offline {
+        server.cache.lock().unwrap().currencies()
+    } else {
+        match server.currencies().await {
+            Ok(currencies) => currencies,
+            Err(ConverterError::QuotaExceeded(_)) => server.cache.lock().unwrap().currencies(),
+            Err(err) => return Err(err.into()),
+        }
+    };
+    Ok(Json(Currencies { currencies }))
+}
+
+/// The lock fetching the rates for a base currency, removed from the server
+/// once no request holds or waits for it, so that the locks of every base
+/// currency requested once are not kept
+struct FetchLock<'a> {
+    fetches: &'a Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    base: &'a str,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for FetchLock<'_> {
+    fn drop(&mut self) {
+        let mut fetches = self.fetches.lock().unwrap();
+        // Held by the server and this request only
+        if Arc::strong_count(&self.lock) == 2 {
+            fetches.remove(self.base);
+        }
+    }
+}
+
+impl<S: ConverterDataSource> Server<S> {
+    fn new(
+        config: Config,
+        cache: Cache,
+        cache_duration: u64,
+        memory_entries: usize,
+        offline: bool,
+    ) -> Self {
+        Server {
+            config,
+            cache_duration,
+            offline,
+            cache: Mutex::new(cache),
+            dirty: AtomicBool::new(false),
+            flushing: tokio::sync::Mutex::new(()),
+            memory: Mutex::new(Lru::new(memory_entries)),
+            fetches: Mutex::new(HashMap::new()),
+            currencies: Mutex::new(None),
+            full_fetches: Mutex::new(HashMap::new()),
+            source: PhantomData,
+        }
+    }
+
+    /// Writes the cache to disk if it has new rates, from a copy taken so
+    /// that requests are not held up by the write
+    async fn flush(&self) {
+        let _flushing = self.flushing.lock().await;
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let cache = self.cache.lock().unwrap().clone();
+        let saved = tokio::task::spawn_blocking(move || cache.save().map_err(|x| x.to_string()))
+            .await
+            .unwrap_or_else(|err| Err(err.to_string()));
+        if let Err(err) = saved {
+            self.dirty.store(true, Ordering::Relaxed);
+            eprintln!("Error while saving the cache: {}", err);
+        }
+    }
+
+    /// Resolves aliases and checks that both currency codes are well-formed
+    fn pair(&self, base: &str, target: &str) -> Result<Pair, ApiError> {
+        Ok(Pair {
+            base: self.currency(base)?,
+            target: self.currency(target)?,
+        })
+    }
+
+    /// Resolves an alias and checks that the currency code is made of three
+    /// letters and, once the supported currencies are known, is one of them,
+    /// so that invalid codes are not sent to the source
+    fn currency(&self, code: &str) -> Result<String, ApiError> {
+        let currency = self.config.currency(code);
+        if currency.len() != 3 || !currency.chars().all(|x| x.is_ascii_uppercase()) {
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                format!("invalid currency `{}`, expected e.g. USD", code),
+            ));
+        }
+        if let Some(currencies) = &*self.currencies.lock().unwrap() {
+            if currencies.binary_search(&currency).is_err() {
+                return Err(ApiError(
+                    StatusCode::BAD_REQUEST,
+                    format!("unsupported currency `{}`", code),
+                ));
+            }
+        }
+        Ok(currency)
+    }
+
+    async fn currencies(&self) -> Result<Vec<String>, ConverterError> {
+        if let Some(currencies) = &*self.currencies.lock().unwrap() {
+            return Ok(currencies.clone());
+        }
+        let mut currencies = S::list().await?.currencies;
+        currencies.sort();
+        *self.currencies.lock().unwrap() = Some(currencies.clone());
+        Ok(currencies)
+    }
+
+    fn needs_full_fetch(&self, base: &str) -> bool {
+        let fetched_at = self.full_fetches.lock().unwrap().get(base).copied();
+        !self.offline
+            && !matches!(fetched_at, Some(fetched_at) if now().saturating_sub(fetched_at) < self.cache_duration)
+    }
+
+    fn cached_targets(&self, base: &str) -> Vec<String> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .rates()
+            .into_iter()
+            .filter(|x| x.base == base)
+            .map(|x| x.target)
+            .collect()
+    }
+
+    /// Looks up the rates for the targets in memory, then in the persistent
+    /// cache, leaving out the stale ones unless offline or `stale` is set
+    fn lookup(
+        &self,
+        base: &str,
+        targets: &[String],
+        rates: &mut BTreeMap<String, Rate>,
+        stale: bool,
+    ) {
+        let is_usable = |rate: &Rate| {
+            self.offline || stale || now().saturating_sub(rate.fetched_at) < self.cache_duration
+        };
+        let mut memory = self.memory.lock().unwrap();
+        let cache = self.cache.lock().unwrap();
+        for target in targets {
+            let pair = Pair {
+                base: base.to_string(),
+                target: target.clone(),
+            };
+            if let Some(rate) = memory.get(&pair).filter(|x| is_usable(x)) {
+                metrics::cache_hit("memory");
+                rates.insert(target.clone(), rate.clone());
+                continue;
+            }
+            let rate = match cache.get_stale(base, target, None, Staleness::Fetched) {
+                Some((converter, age)) => Rate {
+                    rate: converter.rate,
+                    source: converter.source,
+                    updated_at: converter.updated_at,
+                    fetched_at: now().saturating_sub(age),
+                },
+                None => continue,
+            };
+            if is_usable(&rate) {
+                metrics::cache_hit("cache");
+                memory.insert(pair, rate.clone());
+                rates.insert(target.clone(), rate);
+            }
+        }
+    }
+
+    /// Returns the rates for the targets, fetching the stale ones with a
+    /// single request and leaving out the ones that are not available. Once
+    /// the quota is used up the stale rates are returned instead, as offline
+    async fn rates(
+        &self,
+        base: &str,
+        targets: &[String],
+    ) -> Result<BTreeMap<String, Rate>, ConverterError> {
+        let mut rates = BTreeMap::new();
+        self.lookup(base, targets, &mut rates, false);
+        if self.offline {
+            for _ in rates.len()..targets.len() {
+                metrics::cache_miss();
+            }
+            return Ok(rates);
+        }
+        if rates.len() == targets.len() {
+            return Ok(rates);
+        }
+
+        let fetch = FetchLock {
+            fetches: &self.fetches,
+            base,
+            lock: self
+                .fetches
+                .lock()
+                .unwrap()
+                .entry(base.to_string())
+                .or_default()
+                .clone(),
+        };
+        let _fetching = fetch.lock.lock().await;
+        // Another request may have fetched the missing rates while this one
+        // was waiting
+        let missing: Vec<String> = targets
+            .iter()
+            .filter(|x| !rates.contains_key(*x))
+            .cloned()
+            .collect();
+        self.lookup(base, &missing, &mut rates, false);
+        let missing: Vec<String> = missing
+            .into_iter()
+            .filter(|x| !rates.contains_key(x))
+            .collect();
+        if missing.is_empty() {
+            return Ok(rates);
+        }
+
+        for _ in &missing {
+            metrics::cache_miss();
+        }
+        let converters = match S::load_many(base, &missing).await {
+            Ok(val) => val,
+            Err(ConverterError::QuotaExceeded(err)) => {
+                self.lookup(base, &missing, &mut rates, true);
+                if rates.is_empty() {
+                    return Err(ConverterError::QuotaExceeded(err));
+                }
+                return Ok(rates);
+            }
+            Err(err) => return Err(err),
+        };
+        let mut memory = self.memory.lock().unwrap();
+        let mut cache = self.cache.lock().unwrap();
+        for converter in converters {
+            cache.set(&converter);
+            let rate = Rate {
+                rate: converter.rate,
+                source: converter.source,
+                updated_at: converter.updated_at,
+                fetched_at: now(),
+            };
+            let pair = Pair {
+                base: converter.base,
+                target: converter.target,
+            };
+            rates.insert(pair.target.clone(), rate.clone());
+            memory.insert(pair, rate);
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(rates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use std::sync::atomic::AtomicUsize;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{converter::Converter, sources::CurrencyList};
+
+    /// The number of times the rates were fetched from `Counting`
+    static FETCHES: AtomicUsize = AtomicUsize::new(0);
+
+    /// A source answering every request with the same rate, slowly enough for
+    /// concurrent requests to overlap
+    struct Counting;
+
+    impl ConverterDataSource for Counting {
+        const NAME: &'static str = "counting";
+
+        async fn load(_: &str, _: &str) -> Result<Converter, ConverterError> {
+            Err(ConverterError::FetchError(
+                "not used in this test".to_string(),
+            ))
+        }
+
+        async fn load_many(
+            base: &str,
+            targets: &[String],
+        ) -> Result<Vec<Converter>, ConverterError> {
+            FETCHES.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(targets
+                .iter()
+                .map(|target| {
+                    Converter::new(
+                        base.to_string(),
+                        target.clone(),
+                        2.0,
+                        Self::NAME.to_string(),
+                        None,
+                    )
+                })
+                .collect())
+        }
+
+        async fn historical(_: &str, _: &str, _: NaiveDate) -> Result<Converter, ConverterError> {
+            Err(ConverterError::FetchError(
+                "not used in this test".to_string(),
+            ))
+        }
+
+        async fn timeseries(
+            _: &str,
+            _: &str,
+            _: NaiveDate,
+            _: NaiveDate,
+        ) -> Result<Vec<(NaiveDate, Converter)>, ConverterError> {
+            Err(ConverterError::FetchError(
+                "not used in this test".to_string(),
+            ))
+        }
+
+        async fn list() -> Result<CurrencyList, ConverterError> {
+            Err(ConverterError::FetchError(
+                "not used in this test".to_string(),
+            ))
+        }
+    }
+
+    fn server() -> Server {
+        server_with(Config::default())
+    }
+
+    fn server_with(config: Config) -> Server {
+        let mut cache = Cache::new();
+        for (target, rate) in [("EUR", 0.9), ("PLN", 4.0)] {
+            cache.set(&Converter::new(
+                "USD".to_string(),
+                target.to_string(),
+                rate,
+                "test".to_string(),
+                Some(1700000000),
+            ));
+        }
+        Server::new(config, cache, 300, 10, true)
+    }
+
+    async fn get(uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = router(Arc::new(server())).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn endpoints() {
+        let (status, body) = get("/convert?from=usd&to=PLN&amount=10").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["to"], "PLN");
+        assert_eq!(body["result"], 40.0);
+        assert_eq!(body["source"], "test");
+        assert_eq!(body["updated_at"], 1700000000);
+
+        let (status, body) = get("/rates/USD").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["rates"]["EUR"]["rate"], 0.9);
+        assert_eq!(body["rates"].as_object().unwrap().len(), 2);
+
+        let (_, body) = get("/rates/USD?symbols=eur,GBP").await;
+        assert_eq!(body["rates"].as_object().unwrap().len(), 1);
+
+        let (status, body) = get("/currencies").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["currencies"], serde_json::json!(["EUR", "PLN", "USD"]));
+
+        let (status, body) = get("/convert?from=USD&to=GBP").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["error"].is_string());
+        let (status, _) = get("/convert?from=U$D&to=EUR").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get("/convert?from=USDT&to=EUR").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get("/convert?from=%C3%9CSD&to=EUR").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = get("/convert?from=USD&to=EUR&amount=abc").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+        let (status, body) = get("/convert?from=USD").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+        let (status, body) = get("/rates/USD%26apikey=x").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn single_flight() {
+        let server: Server<Counting> = Server::new(Config::default(), Cache::new(), 300, 10, false);
+        let targets = vec!["EUR".to_string(), "PLN".to_string()];
+        let results = tokio::join!(
+            server.rates("USD", &targets),
+            server.rates("USD", &targets),
+            server.rates("USD", &targets),
+            server.rates("USD", &targets),
+            server.rates("USD", &targets),
+        );
+        for rates in [results.0, results.1, results.2, results.3, results.4] {
+            let rates = rates.unwrap();
+            assert_eq!(rates.len(), 2);
+            assert_eq!(rates["PLN"].source, "counting");
+        }
+        assert_eq!(FETCHES.load(Ordering::SeqCst), 1);
+        assert!(server.dirty.load(Ordering::Relaxed));
+        assert!(server.fetches.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn supported_currencies() {
+        let server = server();
+        assert!(server.pair("usd", "XYZ").is_ok());
+        *server.currencies.lock().unwrap() = Some(vec!["EUR".to_string(), "USD".to_string()]);
+        assert!(server.pair("usd", "eur").is_ok());
+        assert!(server.pair("USD", "XYZ").is_err());
+    }
+
+    #[tokio::test]
+    async fn watchlist_metrics() {
+        metrics::install();
+        let config: Config = toml::from_str(r#"watchlist = ["USD/PLN", "USD/GBP"]"#).unwrap();
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = router(Arc::new(server_with(config)))
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains(r#"currency_converter_rate{base="USD",target="PLN"} 4"#));
+        assert!(!text.contains(r#"target="GBP""#));
+    }
+}
@@ -0,0 +1,68 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// A map holding at most `capacity` entries, evicting the least recently
+/// used one to make room for a new one
+#[derive(Debug)]
+pub struct Lru<K, V> {
+    capacity: usize,
+    /// Incremented on every access, entries remember when they were last used
+    clock: u64,
+    entries: HashMap<K, (V, u64)>,
+}
+
+impl<K: Eq + Hash + Clone, V> Lru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Lru {
+            capacity: capacity.max(1),
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(key).map(|(value, used)| {
+            *used = clock;
+            &*value
+        })
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.clock += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            // A linear scan is fine for the few thousand rates that exist
+            if let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone())
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (value, self.clock));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert("USD/EUR", 0.9);
+        lru.insert("USD/PLN", 4.0);
+        assert_eq!(lru.get(&"USD/EUR"), Some(&0.9));
+
+        lru.insert("USD/GBP", 0.8);
+        assert_eq!(lru.get(&"USD/PLN"), None);
+        assert_eq!(lru.get(&"USD/EUR"), Some(&0.9));
+
+        // Replacing an entry does not evict another one
+        lru.insert("USD/GBP", 0.79);
+        assert_eq!(lru.get(&"USD/GBP"), Some(&0.79));
+        assert_eq!(lru.get(&"USD/EUR"), Some(&0.9));
+    }
+}
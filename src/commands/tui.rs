@@ -0,0 +1,529 @@
+use std::io::{self, Stdout};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::{
+    cache::Cache,
+    config::Config,
+    converter::{Converter, Pair},
+    helpers::{error_and_exit, format_timestamp},
+    sources::{currency_api::CurrencyApi, ConverterDataSource},
+    InteractiveArgs,
+};
+
+use super::get_rate;
+
+mod picker;
+
+use picker::Picker;
+
+/// The number of conversions kept in the history pane
+const HISTORY_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Base,
+    Target,
+    Amount,
+    Favorites,
+}
+
+impl Focus {
+    fn next(self) -> Self {
+        match self {
+            Focus::Base => Focus::Target,
+            Focus::Target => Focus::Amount,
+            Focus::Amount => Focus::Favorites,
+            Focus::Favorites => Focus::Base,
+        }
+    }
+
+    fn previous(self) -> Self {
+        match self {
+            Focus::Base => Focus::Favorites,
+            Focus::Target => Focus::Base,
+            Focus::Amount => Focus::Target,
+            Focus::Favorites => Focus::Amount,
+        }
+    }
+}
+
+struct App {
+    focus: Focus,
+    base: Picker,
+    target: Picker,
+    amount: String,
+    precision: usize,
+    /// The rate of the current pair, once it has been loaded
+    converter: Option<Converter>,
+    /// Set when the pair changed and its rate has to be loaded
+    stale: bool,
+    favorites: Vec<Pair>,
+    favorite: usize,
+    /// Past conversions, newest first
+    history: Vec<String>,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    fn new(currencies: &[String], favorites: Vec<Pair>, precision: usize) -> Self {
+        let (base, target) = match favorites.first() {
+            Some(pair) => (pair.base.as_str(), pair.target.as_str()),
+            None => ("USD", "EUR"),
+        };
+        App {
+            focus: Focus::Amount,
+            base: Picker::new(base, currencies),
+            target: Picker::new(target, currencies),
+            amount: "1".to_string(),
+            precision,
+            converter: None,
+            stale: true,
+            favorites,
+            favorite: 0,
+            history: Vec::new(),
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    fn pair(&self) -> Pair {
+        Pair {
+            base: self.base.value.clone(),
+            target: self.target.value.clone(),
+        }
+    }
+
+    /// Converts the amount with the rate of the current pair, if both are
+    /// available
+    fn result(&self) -> Option<(f64, &Converter)> {
+        let amount: f64 = self.amount.parse().ok()?;
+        let converter = self.converter.as_ref()?;
+        (converter.base == self.base.value && converter.target == self.target.value)
+            .then_some((converter.convert(amount), converter))
+    }
+
+    fn set_pair(&mut self, pair: &Pair) {
+        self.base.set_value(&pair.base);
+        self.target.set_value(&pair.target);
+        self.stale = true;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('q') if control => self.quit = true,
+            KeyCode::Char('s') if control => {
+                self.set_pair(&Pair {
+                    base: self.target.value.clone(),
+                    target: self.base.value.clone(),
+                });
+            }
+            KeyCode::Char('f') if control => self.toggle_favorite(),
+            KeyCode::Tab => {
+                self.confirm_pickers();
+                self.focus = self.focus.next();
+            }
+            KeyCode::BackTab => {
+                self.confirm_pickers();
+                self.focus = self.focus.previous();
+            }
+            _ => match self.focus {
+                Focus::Base | Focus::Target => self.handle_picker_key(key),
+                Focus::Amount => self.handle_amount_key(key),
+                Focus::Favorites => self.handle_favorites_key(key),
+            },
+        }
+    }
+
+    fn handle_picker_key(&mut self, key: KeyEvent) {
+        let picker = match self.focus {
+            Focus::Base => &mut self.base,
+            _ => &mut self.target,
+        };
+        match key.code {
+            KeyCode::Char(c) if c.is_alphanumeric() => picker.push(c),
+            KeyCode::Backspace => picker.pop(),
+            KeyCode::Down => picker.select_next(),
+            KeyCode::Up => picker.select_previous(),
+            KeyCode::Esc if !picker.query.is_empty() => picker.clear(),
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Enter => {
+                self.stale |= picker.confirm();
+                self.focus = self.focus.next();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_amount_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() || c == '.' => self.amount.push(c),
+            KeyCode::Backspace => {
+                self.amount.pop();
+            }
+            KeyCode::Enter => match self.result() {
+                Some((result, converter)) => {
+                    let entry = format!(
+                        "{} {} = {:.precision$} {}",
+                        self.amount,
+                        converter.base,
+                        result,
+                        converter.target,
+                        precision = self.precision
+                    );
+                    self.history.insert(0, entry);
+                    self.history.truncate(HISTORY_SIZE);
+                }
+                None => self.status = "Nothing to convert yet.".to_string(),
+            },
+            KeyCode::Esc => self.quit = true,
+            _ => {}
+        }
+    }
+
+    fn handle_favorites_key(&mut self, key: KeyEvent) {
+        let count = self.favorites.len();
+        match key.code {
+            KeyCode::Down if count > 0 => self.favorite = (self.favorite + 1) % count,
+            KeyCode::Up if count > 0 => self.favorite = (self.favorite + count - 1) % count,
+            KeyCode::Enter => {
+                if let Some(pair) = self.favorites.get(self.favorite).cloned() {
+                    self.set_pair(&pair);
+                    self.focus = Focus::Amount;
+                }
+            }
+            KeyCode::Delete | KeyCode::Backspace if self.favorite < count => {
+                let pair = self.favorites.remove(self.favorite);
+                self.favorite = self.favorite.min(self.favorites.len().saturating_sub(1));
+                self.status = format!("Removed {} from the favorites.", pair);
+            }
+            KeyCode::Esc => self.quit = true,
+            _ => {}
+        }
+    }
+
+    /// Confirms any search typed into the pickers before leaving them
+    fn confirm_pickers(&mut self) {
+        for picker in [&mut self.base, &mut self.target] {
+            if !picker.query.is_empty() {
+                self.stale |= picker.confirm();
+            }
+        }
+    }
+
+    fn toggle_favorite(&mut self) {
+        let pair = self.pair();
+        match self.favorites.iter().position(|x| *x == pair) {
+            Some(index) => {
+                self.favorites.remove(index);
+                self.status = format!("Removed {} from the favorites.", pair);
+            }
+            None => {
+                self.status = format!("Added {} to the favorites.", pair);
+                self.favorites.push(pair);
+            }
+        }
+        self.favorite = self.favorite.min(self.favorites.len().saturating_sub(1));
+    }
+}
+
+pub async fn tui(args: &InteractiveArgs, mut config: Config, offline: bool) -> ! {
+    let mut cache = match Cache::load() {
+        Ok(val) => val,
+        Err(_) => Cache::new(),
+    };
+    let currencies = if offline {
+        cache.currencies()
+    } else {
+        match CurrencyApi::list().await {
+            Ok(val) => val.currencies,
+            Err(_) => cache.currencies(),
+        }
+    };
+
+    let mut terminal = match setup_terminal() {
+        Ok(val) => val,
+        Err(err) => error_and_exit(&err),
+    };
+    let precision = config.precision(args.precision);
+    let cache_duration = config.cache_duration(args.cache_duration);
+    let mut app = App::new(&currencies, config.favorites.clone(), precision);
+    let result = run(&mut terminal, &mut app, &mut cache, cache_duration, offline).await;
+    if let Err(err) = restore_terminal() {
+        eprintln!("Error while restoring the terminal: {}", err);
+    }
+    if let Err(err) = result {
+        error_and_exit(&err)
+    }
+
+    if let Err(err) = cache.save() {
+        error_and_exit(&err)
+    }
+    if app.favorites != config.favorites {
+        config.favorites = app.favorites;
+        if let Err(err) = config.save() {
+            error_and_exit(&err)
+        }
+    }
+    std::process::exit(0);
+}
+
+fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    // Leave the terminal usable if anything panics while it is in raw mode
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        hook(info);
+    }));
+    Terminal::new(CrosstermBackend::new(io::stdout()))
+}
+
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)
+}
+
+async fn run(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    cache: &mut Cache,
+    cache_duration: u64,
+    offline: bool,
+) -> io::Result<()> {
+    while !app.quit {
+        if app.stale {
+            app.stale = false;
+            let pair = app.pair();
+            app.status = format!("Getting the {} rate...", pair);
+            terminal.draw(|frame| draw(frame, app))?;
+            app.status =
+                match get_rate(cache, &pair.base, &pair.target, cache_duration, offline).await {
+                    Ok(converter) => {
+                        app.converter = Some(converter);
+                        String::new()
+                    }
+                    Err(err) => {
+                        app.converter = None;
+                        err.to_string()
+                    }
+                };
+        }
+
+        terminal.draw(|frame| draw(frame, app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [inputs, result, panels, help] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(4),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.size());
+    let [base, target, amount] = Layout::horizontal([
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Min(16),
+    ])
+    .areas(inputs);
+
+    draw_picker(frame, base, "From", &app.base, app.focus == Focus::Base);
+    draw_picker(frame, target, "To", &app.target, app.focus == Focus::Target);
+    frame.render_widget(
+        Paragraph::new(app.amount.as_str()).block(block("Amount", app.focus == Focus::Amount)),
+        amount,
+    );
+    if app.focus == Focus::Amount {
+        frame.set_cursor(amount.x + 1 + app.amount.len() as u16, amount.y + 1);
+    }
+
+    let mut lines = match app.result() {
+        Some((result, converter)) => {
+            let mut lines = vec![
+                Line::from(Span::styled(
+                    format!(
+                        "{} {} = {:.precision$} {}",
+                        app.amount,
+                        converter.base,
+                        result,
+                        converter.target,
+                        precision = app.precision
+                    ),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Line::from(format!(
+                    "1 {} ~= {:.precision$} {}",
+                    converter.base,
+                    converter.rate,
+                    converter.target,
+                    precision = app.precision + 2
+                )),
+            ];
+            if let Some(updated_at) = converter.updated_at {
+                lines.push(Line::from(format!(
+                    "Rate as of {} ({}).",
+                    format_timestamp(updated_at),
+                    converter.source
+                )));
+            }
+            lines
+        }
+        None => Vec::new(),
+    };
+    if !app.status.is_empty() {
+        lines.push(Line::from(Span::styled(
+            app.status.as_str(),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+    frame.render_widget(Paragraph::new(lines), result);
+
+    let [currencies, favorites, history] = Layout::horizontal([
+        Constraint::Ratio(1, 3),
+        Constraint::Ratio(1, 3),
+        Constraint::Ratio(1, 3),
+    ])
+    .areas(panels);
+
+    let picker = match app.focus {
+        Focus::Target => &app.target,
+        _ => &app.base,
+    };
+    let picking = matches!(app.focus, Focus::Base | Focus::Target);
+    let mut state = ListState::default().with_selected(picking.then_some(picker.selected()));
+    frame.render_stateful_widget(
+        List::new(picker.matches().iter().map(|x| ListItem::new(x.as_str())))
+            .block(block("Currencies", false))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        currencies,
+        &mut state,
+    );
+
+    let mut state =
+        ListState::default().with_selected((app.focus == Focus::Favorites).then_some(app.favorite));
+    frame.render_stateful_widget(
+        List::new(app.favorites.iter().map(|x| ListItem::new(x.to_string())))
+            .block(block("Favorites", app.focus == Focus::Favorites))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        favorites,
+        &mut state,
+    );
+
+    frame.render_widget(
+        List::new(app.history.iter().map(|x| ListItem::new(x.as_str())))
+            .block(block("History", false)),
+        history,
+    );
+
+    frame.render_widget(
+        Paragraph::new(
+            "Tab: next field  Enter: select/save  Ctrl+S: swap  Ctrl+F: favorite  Esc: quit",
+        )
+        .style(Style::default().fg(Color::DarkGray)),
+        help,
+    );
+}
+
+fn draw_picker(frame: &mut Frame, area: Rect, title: &str, picker: &Picker, focused: bool) {
+    let text = if focused && !picker.query.is_empty() {
+        picker.query.as_str()
+    } else {
+        picker.value.as_str()
+    };
+    frame.render_widget(Paragraph::new(text).block(block(title, focused)), area);
+    if focused {
+        frame.set_cursor(area.x + 1 + text.len() as u16, area.y + 1);
+    }
+}
+
+fn block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn app() -> App {
+        let currencies: Vec<String> = ["EUR", "PLN", "USD"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let mut app = App::new(&currencies, Vec::new(), 2);
+        app.stale = false;
+        app.converter = Some(Converter::new(
+            "USD".to_string(),
+            "EUR".to_string(),
+            0.5,
+            "test".to_string(),
+            None,
+        ));
+        app
+    }
+
+    #[test]
+    fn live_conversion() {
+        let mut app = app();
+        press(&mut app, KeyCode::Char('0'));
+        assert_eq!(app.result().unwrap().0, 5.0);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.history, vec!["10 USD = 5.00 EUR"]);
+
+        // Picking a new target needs its rate before converting again
+        press(&mut app, KeyCode::BackTab);
+        press(&mut app, KeyCode::Char('p'));
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.target.value, "PLN");
+        assert!(app.stale);
+        assert!(app.result().is_none());
+    }
+
+    #[test]
+    fn favorites() {
+        let mut app = app();
+        app.handle_key(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        assert_eq!(app.favorites, vec!["USD/EUR".parse().unwrap()]);
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert_eq!(app.pair(), "EUR/USD".parse().unwrap());
+
+        app.focus = Focus::Favorites;
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.pair(), "USD/EUR".parse().unwrap());
+        assert_eq!(app.focus, Focus::Amount);
+    }
+}
@@ -0,0 +1,177 @@
+/// A currency field whose value is chosen from a list filtered by a fuzzy
+/// search query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Picker {
+    pub value: String,
+    pub query: String,
+    items: Vec<String>,
+    /// The items matching the query, best match first
+    matches: Vec<String>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(value: &str, items: &[String]) -> Self {
+        let mut picker = Picker {
+            value: value.to_uppercase(),
+            query: String::new(),
+            items: items.to_vec(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        picker.filter();
+        picker
+    }
+
+    pub fn matches(&self) -> &[String] {
+        &self.matches
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.to_string();
+        self.clear();
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.filter();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.filter();
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.filter();
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /// Sets the value to the selected match, returning whether it changed.
+    /// An unknown code typed in full is accepted as well, as the list of
+    /// currencies may be incomplete when offline.
+    pub fn confirm(&mut self) -> bool {
+        let value = match self.matches.get(self.selected) {
+            Some(val) => val.clone(),
+            None if self.query.chars().all(|x| x.is_alphanumeric()) => self.query.to_uppercase(),
+            None => return false,
+        };
+        self.clear();
+        if value.is_empty() || value == self.value {
+            return false;
+        }
+        self.value = value;
+        true
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(u32, &String)> = self
+            .items
+            .iter()
+            .filter_map(|item| fuzzy_score(&self.query, item).map(|score| (score, item)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        self.matches = scored.into_iter().map(|(_, item)| item.clone()).collect();
+        self.selected = if self.query.is_empty() {
+            self.matches
+                .iter()
+                .position(|x| *x == self.value)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+    }
+}
+
+/// Scores how well the query matches the candidate, case-insensitively, or
+/// returns `None` if the characters of the query do not appear in the
+/// candidate in order. Consecutive matches and matches at the start score
+/// higher.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut candidate = candidate
+        .chars()
+        .map(|x| x.to_ascii_lowercase())
+        .enumerate();
+    for c in query.chars().map(|x| x.to_ascii_lowercase()) {
+        let (index, _) = candidate.find(|(_, x)| *x == c)?;
+        score += match previous {
+            _ if index == 0 => 10,
+            Some(previous) if index == previous + 1 => 5,
+            _ => 1,
+        };
+        previous = Some(index);
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn currencies() -> Vec<String> {
+        ["EUR", "GBP", "PLN", "USD", "UAH"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn score() {
+        assert_eq!(fuzzy_score("", "USD"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "USD"), None);
+        assert_eq!(fuzzy_score("du", "USD"), None);
+        assert!(fuzzy_score("us", "USD") > fuzzy_score("ud", "USD"));
+        assert!(fuzzy_score("u", "USD") > fuzzy_score("u", "EUR"));
+    }
+
+    #[test]
+    fn filter_and_confirm() {
+        let mut picker = Picker::new("usd", &currencies());
+        assert_eq!(picker.value, "USD");
+        assert_eq!(picker.matches().len(), 5);
+        assert_eq!(picker.matches()[picker.selected()], "USD");
+
+        picker.push('u');
+        assert_eq!(picker.matches(), ["UAH", "USD", "EUR"]);
+        picker.select_next();
+        picker.select_next();
+        assert!(picker.confirm());
+        assert_eq!(picker.value, "EUR");
+        assert!(picker.query.is_empty());
+
+        picker.push('p');
+        assert_eq!(picker.matches(), ["PLN", "GBP"]);
+        picker.select_previous();
+        assert_eq!(picker.matches()[picker.selected()], "GBP");
+        picker.pop();
+        assert_eq!(picker.matches()[picker.selected()], "EUR");
+        assert!(!picker.confirm());
+    }
+
+    #[test]
+    fn confirm_unknown_code() {
+        let mut picker = Picker::new("USD", &currencies());
+        "chf".chars().for_each(|x| picker.push(x));
+        assert!(picker.matches().is_empty());
+        assert!(picker.confirm());
+        assert_eq!(picker.value, "CHF");
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, time::Duration};
+
+use spinoff::{spinners, Color, Spinner};
+
+use crate::{
+    cache::{Cache, Staleness},
+    config::Config,
+    helpers::{error_and_exit, format_duration, format_timestamp, now, ConverterError},
+    series::RateChange,
+    sources::{currency_api::CurrencyApi, ConverterDataSource},
+    WatchArgs,
+};
+
+/// A line of the board, for a target currency with a known rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Row {
+    /// The rate when the board was started
+    start: f64,
+    rate: f64,
+    /// Seconds since the rate was fetched
+    age: u64,
+}
+
+pub async fn watch(args: &WatchArgs, config: &Config, offline: bool) -> ! {
+    let base = config.currency(&args.base);
+    let mut targets: Vec<String> = Vec::new();
+    for target in &args.targets {
+        let target = config.currency(target);
+        if target != base && !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    if targets.is_empty() {
+        error_and_exit("No target currencies to watch.")
+    }
+
+    let mut start_rates: HashMap<String, f64> = HashMap::new();
+    let mut requests = 0;
+    loop {
+        // Reloaded on every cycle so rates fetched by other commands are shown
+        let mut cache = match Cache::load() {
+            Ok(val) => val,
+            Err(_) => Cache::new(),
+        };
+
+        let stale: Vec<String> = targets
+            .iter()
+            .filter(|target| !cache.is_fresh(&base, target, args.interval, Staleness::Fetched))
+            .cloned()
+            .collect();
+        let budget_left = !matches!(args.max_requests, Some(max) if requests >= max);
+        let status = if offline {
+            "Offline mode, only showing cached rates.".to_string()
+        } else if stale.is_empty() {
+            "All rates are fresh.".to_string()
+        } else if !budget_left {
+            format!(
+                "The budget of {} request(s) was reached, only showing cached rates.",
+                requests
+            )
+        } else {
+            let mut spinner = Spinner::new(spinners::Dots, "Refreshing the rates...", Color::White);
+            let result = CurrencyApi::load_many(&base, &stale).await;
+            // No request is made while the quota is used up
+            if !matches!(result, Err(ConverterError::QuotaExceeded(_))) {
+                requests += 1;
+            }
+            let status = match result {
+                Ok(converters) => {
+                    for converter in &converters {
+                        cache.set(converter);
+                    }
+                    // The board keeps running, the rates that could not be
+                    // saved are fetched again with the next refresh
+                    match cache.save() {
+                        Ok(_) => format!("Refreshed {} rate(s).", converters.len()),
+                        Err(err) => format!(
+                            "Refreshed {} rate(s), but failed to save the cache: {}",
+                            converters.len(),
+                            err
+                        ),
+                    }
+                }
+                Err(ConverterError::QuotaExceeded(_)) => {
+                    "The monthly quota is used up, only showing cached rates.".to_string()
+                }
+                Err(err) => format!("Failed to refresh the rates: {}", err),
+            };
+            spinner.clear();
+            status
+        };
+
+        let rows: Vec<(String, Option<Row>)> = targets
+            .iter()
+            .map(|target| {
+                let row = cache
+                    .get_stale(&base, target, None, Staleness::Fetched)
+                    .map(|(converter, age)| Row {
+                        start: *start_rates.entry(target.clone()).or_insert(converter.rate),
+                        rate: converter.rate,
+                        age,
+                    });
+                (target.clone(), row)
+            })
+            .collect();
+
+        // Clear the screen and move the cursor to the top left corner
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "{} rates, refreshing every {} (Ctrl+C to quit)\n",
+            base,
+            format_duration(args.interval)
+        );
+        print!("{}", render_board(&base, &rows, args.precision));
+        // When the newest of the shown rates was fetched, by this or another
+        // command
+        let updated = rows
+            .iter()
+            .filter_map(|(_, row)| row.map(|x| x.age))
+            .min()
+            .map_or("never".to_string(), |age| {
+                format_timestamp(now().saturating_sub(age))
+            });
+        println!("\nLast update: {}, {} request(s) made", updated, requests);
+        println!("{}", status);
+
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+    }
+}
+
+fn render_board(base: &str, rows: &[(String, Option<Row>)], precision: usize) -> String {
+    let mut board = format!(
+        "{:<9} {:>14} {:>14} {:>9} {:>9}\n",
+        "Pair", "Rate", "Change", "Change %", "Age"
+    );
+    for (target, row) in rows {
+        let pair = format!("{}/{}", base, target);
+        board.push_str(&match row {
+            Some(row) => {
+                let change = RateChange::new(row.start, row.rate);
+                format!(
+                    "{:<9} {:>14.precision$} {:>+14.precision$} {:>+8.2}% {:>9}\n",
+                    pair,
+                    row.rate,
+                    change.absolute,
+                    change.percent,
+                    format_duration(row.age),
+                    precision = precision
+                )
+            }
+            None => format!(
+                "{:<9} {:>14} {:>14} {:>9} {:>9}\n",
+                pair, "-", "-", "-", "-"
+            ),
+        });
+    }
+    board
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board() {
+        let rows = vec![
+            (
+                "PLN".to_string(),
+                Some(Row {
+                    start: 4.0,
+                    rate: 4.1,
+                    age: 65,
+                }),
+            ),
+            ("EUR".to_string(), None),
+        ];
+        let board = render_board("USD", &rows, 4);
+        let lines: Vec<&str> = board.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1].split_whitespace().collect::<Vec<_>>(),
+            vec!["USD/PLN", "4.1000", "+0.1000", "+2.50%", "1m", "5s"]
+        );
+        assert!(lines[2].starts_with("USD/EUR"));
+        assert!(lines[2].ends_with('-'));
+    }
+}
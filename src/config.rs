@@ -0,0 +1,587 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item};
+
+use crate::{
+    alert::Alert,
+    cache::{Retention, DEFAULT_HISTORY_DAYS, DEFAULT_HISTORY_ENTRIES},
+    converter::Pair,
+    helpers::{OutputFormat, Secret},
+    quota::{Limits, DEFAULT_WARNINGS},
+    sources::{
+        http::{
+            HttpOptions, DEFAULT_CONNECT_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, DEFAULT_RETRIES,
+            DEFAULT_USER_AGENT,
+        },
+        SOURCES,
+    },
+};
+
+pub const DEFAULT_PRECISION: usize = 2;
+pub const DEFAULT_CACHE_DURATION: u64 = 300;
+
+/// Settings read from the config file. Flags and environment variables take
+/// precedence over the settings of the selected profile, which take
+/// precedence over the top-level settings, which take precedence over the
+/// built-in defaults.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub settings: Settings,
+    /// Pairs kept fresh in the cache by the `refresh` command
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub watchlist: Vec<Pair>,
+    /// Pairs listed in the favorites panel of the interactive interface
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub favorites: Vec<Pair>,
+    /// Command run by `alert check` when any alert triggers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_command: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<Alert>,
+    /// Named sets of settings selected with `--profile`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Settings>,
+
+    /// The file the config was loaded from and is saved to
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// The settings of the selected profile
+    #[serde(skip)]
+    profile: Option<Settings>,
+}
+
+/// The settings that can differ between profiles
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Only use cached rates and never make requests to the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline: Option<bool>,
+    /// The precision to use when displaying converted amounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<usize>,
+    /// The duration to cache the exchange rates for, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_duration: Option<u64>,
+    /// How many days the past rates of every pair are kept in the cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_days: Option<u64>,
+    /// How many past rates of every pair are kept in the cache at most
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_entries: Option<usize>,
+    /// The output format of commands that support several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+    /// The sources to use, in order of preference
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<Secret>,
+    /// A file holding the API key, used when `api_key` is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
+    /// Alternative names of currencies, e.g. `zloty = "PLN"`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// The currency converted into when only one currency is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
+    /// The number of API requests allowed per month
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<u64>,
+    /// The percentages of the quota at which to warn
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub quota_warnings: Vec<u64>,
+    /// Refuse API requests once the quota is used up, using cached rates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_enforce: Option<bool>,
+    /// The maximum time to wait for a connection to the API, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    /// The maximum time a request to the API may take as a whole, from
+    /// connecting to reading the end of the response, in seconds
+    #[serde(alias = "read_timeout", skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<u64>,
+    /// How many times to retry a failed request to the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// The proxy to send requests to the API through, e.g.
+    /// `http://proxy.example.com:3128`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<Secret>,
+    /// A PEM file with certificates to trust, e.g. of a TLS-inspecting proxy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Headers sent with every request to the API
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, Secret>,
+}
+
+impl Settings {
+    /// Returns the API key, reading it from the key file if needed
+    fn api_key(&self) -> Result<Option<String>, Box<dyn Error>> {
+        if let Some(key) = &self.api_key {
+            return Ok(Some(key.expose().to_string()));
+        }
+        match &self.api_key_file {
+            Some(path) => {
+                let key = fs::read_to_string(path).map_err(|err| {
+                    format!(
+                        "Error while reading the API key from {}: {}",
+                        path.display(),
+                        err
+                    )
+                })?;
+                Ok(Some(key.trim().to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Config {
+    /// The default location of the config file
+    pub fn default_path() -> Result<PathBuf, Box<dyn Error>> {
+        let path = dirs::config_dir().ok_or("Could not determine the config directory")?;
+        Ok(path.join("currency-converter").join("config.toml"))
+    }
+
+    /// Loads the config file, the default one unless a path is given, falling
+    /// back to the defaults if it does not exist
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => Self::default_path()?,
+        };
+        if !path.exists() {
+            return Ok(Config {
+                path,
+                ..Default::default()
+            });
+        }
+        let contents = fs::read_to_string(&path)?;
+        let mut config: Config = toml::from_str(&contents)
+            .map_err(|err| format!("Error while parsing {}: {}", path.display(), err))?;
+        if let Some(source) = std::iter::once(&config.settings)
+            .chain(config.profiles.values())
+            .flat_map(|x| &x.sources)
+            .find(|x| !SOURCES.contains(&x.as_str()))
+        {
+            return Err(format!(
+                "Error while parsing {}: unknown source `{}`, available sources: {}",
+                path.display(),
+                source,
+                SOURCES.join(", ")
+            )
+            .into());
+        }
+        config.path = path;
+        Ok(config)
+    }
+
+    /// Writes the favorites and the alerts, the settings changed by the
+    /// commands, to the config file, leaving the rest of it as it was
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(val) => val,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let contents = self
+            .update(&contents)
+            .map_err(|err| format!("Error while saving {}: {}", self.path.display(), err))?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, contents)?;
+        Ok(())
+    }
+
+    /// Replaces the favorites and the alerts in the contents of a config file,
+    /// keeping its comments and formatting
+    fn update(&self, contents: &str) -> Result<String, Box<dyn Error>> {
+        let mut document: DocumentMut = contents.parse()?;
+        let saved = toml_edit::ser::to_document(self)?;
+        for key in ["favorites", "alerts"] {
+            match saved.get(key) {
+                // Written as `[[alerts]]` tables rather than inline ones
+                Some(item) => {
+                    document[key] = item
+                        .clone()
+                        .into_array_of_tables()
+                        .map_or_else(|x| x, Item::ArrayOfTables)
+                }
+                None => {
+                    document.remove(key);
+                }
+            }
+        }
+        Ok(document.to_string())
+    }
+
+    /// Selects the profile whose settings take precedence over the
+    /// top-level ones
+    pub fn select_profile(&mut self, name: &str) -> Result<(), String> {
+        // The name is part of the cache file name
+        if !name
+            .chars()
+            .all(|x| x.is_alphanumeric() || x == '-' || x == '_')
+        {
+            return Err(format!(
+                "Invalid profile name `{}`, only letters, digits, `-` and `_` are allowed",
+                name
+            ));
+        }
+        match self.profiles.get(name) {
+            Some(profile) => {
+                self.profile = Some(profile.clone());
+                Ok(())
+            }
+            None if self.profiles.is_empty() => Err(format!(
+                "Unknown profile `{}`, no profiles are defined in {}",
+                name,
+                self.path.display()
+            )),
+            None => Err(format!(
+                "Unknown profile `{}`, available profiles: {}",
+                name,
+                self.profiles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// The settings to look at, the selected profile first
+    fn layers(&self) -> impl Iterator<Item = &Settings> {
+        self.profile.iter().chain(std::iter::once(&self.settings))
+    }
+
+    /// Whether to only use cached rates, either because of the flag or the
+    /// config file
+    pub fn offline(&self, flag: bool) -> bool {
+        flag || self.layers().find_map(|x| x.offline).unwrap_or(false)
+    }
+
+    pub fn precision(&self, flag: Option<usize>) -> usize {
+        flag.or_else(|| self.layers().find_map(|x| x.precision))
+            .unwrap_or(DEFAULT_PRECISION)
+    }
+
+    pub fn cache_duration(&self, flag: Option<u64>) -> u64 {
+        flag.or_else(|| self.layers().find_map(|x| x.cache_duration))
+            .unwrap_or(DEFAULT_CACHE_DURATION)
+    }
+
+    pub fn format(&self, flag: Option<OutputFormat>) -> OutputFormat {
+        flag.or_else(|| self.layers().find_map(|x| x.format))
+            .unwrap_or_default()
+    }
+
+    /// The preferred source
+    pub fn source(&self) -> &str {
+        self.layers()
+            .find_map(|x| x.sources.first())
+            .map_or(SOURCES[0], String::as_str)
+    }
+
+    /// Resolves an alias to the currency code it stands for, or uppercases
+    /// the code
+    pub fn currency(&self, code: &str) -> String {
+        self.layers()
+            .flat_map(|x| &x.aliases)
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(code))
+            .map_or(code, |(_, currency)| currency.as_str())
+            .to_uppercase()
+    }
+
+    pub fn home(&self) -> Option<String> {
+        self.layers()
+            .find_map(|x| x.home.as_deref())
+            .map(|x| self.currency(x))
+    }
+
+    /// How long the past rates of every pair are kept
+    pub fn retention(&self) -> Retention {
+        let days = self
+            .layers()
+            .find_map(|x| x.history_days)
+            .unwrap_or(DEFAULT_HISTORY_DAYS);
+        Retention {
+            max_age: days * 24 * 60 * 60,
+            max_entries: self
+                .layers()
+                .find_map(|x| x.history_entries)
+                .unwrap_or(DEFAULT_HISTORY_ENTRIES),
+        }
+    }
+
+    /// The monthly budget of API requests
+    pub fn quota(&self) -> Limits {
+        let warnings = self
+            .layers()
+            .map(|x| &x.quota_warnings)
+            .find(|x| !x.is_empty())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_WARNINGS.to_vec());
+        Limits {
+            budget: self.layers().find_map(|x| x.quota),
+            warnings,
+            enforce: self.layers().find_map(|x| x.quota_enforce).unwrap_or(false),
+        }
+    }
+
+    /// How to make requests to the API
+    pub fn http(&self) -> HttpOptions {
+        let seconds = |value: Option<u64>, default| Duration::from_secs(value.unwrap_or(default));
+        HttpOptions {
+            connect_timeout: seconds(
+                self.layers().find_map(|x| x.connect_timeout),
+                DEFAULT_CONNECT_TIMEOUT,
+            ),
+            request_timeout: seconds(
+                self.layers().find_map(|x| x.request_timeout),
+                DEFAULT_REQUEST_TIMEOUT,
+            ),
+            retries: self
+                .layers()
+                .find_map(|x| x.retries)
+                .unwrap_or(DEFAULT_RETRIES),
+            proxy: self.layers().find_map(|x| x.proxy.clone()),
+            ca_file: self.layers().find_map(|x| x.ca_file.clone()),
+            user_agent: self
+                .layers()
+                .find_map(|x| x.user_agent.clone())
+                .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+            // The headers of the profile replace the top-level ones of the same name
+            headers: self
+                .layers()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .flat_map(|x| x.headers.clone())
+                .collect(),
+        }
+    }
+
+    /// Returns the API key, reading it from the key file if needed. Unlike
+    /// the other settings, a key set in the selected profile takes precedence
+    /// over the one from the environment, which takes precedence over the
+    /// top-level one, so that a profile always uses its own account
+    pub fn api_key(&self, env: Option<String>) -> Result<Option<String>, Box<dyn Error>> {
+        if let Some(profile) = &self.profile {
+            if let Some(key) = profile.api_key()? {
+                return Ok(Some(key));
+            }
+        }
+        match env {
+            Some(key) => Ok(Some(key)),
+            None => self.settings.api_key(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::Condition;
+
+    #[test]
+    fn parse_watchlist() {
+        let config: Config = toml::from_str(r#"watchlist = ["USD/EUR", "usd/pln"]"#).unwrap();
+        assert_eq!(
+            config.watchlist,
+            vec!["USD/EUR".parse().unwrap(), "USD/PLN".parse().unwrap()]
+        );
+        assert!(toml::from_str::<Config>(r#"watchlist = ["USD"]"#).is_err());
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn alerts_round_trip() {
+        let config = Config {
+            alerts: vec![
+                Alert {
+                    pair: "USD/PLN".parse().unwrap(),
+                    condition: Condition::Above(4.1),
+                },
+                Alert {
+                    pair: "EUR/PLN".parse().unwrap(),
+                    condition: Condition::Change(2.0),
+                },
+            ],
+            settings: Settings {
+                aliases: BTreeMap::from([("zloty".to_string(), "PLN".to_string())]),
+                precision: Some(4),
+                ..Default::default()
+            },
+            profiles: BTreeMap::from([(
+                "work".to_string(),
+                Settings {
+                    api_key: Some(Secret::from("key".to_string())),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let toml = toml::to_string(&config).unwrap();
+        assert!(toml.contains("above = 4.1"));
+        assert!(!toml.contains("alert_command"));
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn update_keeps_comments() {
+        let contents = r#"# Personal settings
+precision = 4 # more than the default
+favorites = ["USD/EUR"]
+
+[[alerts]]
+pair = "USD/PLN"
+above = 4.1
+
+[aliases]
+# The currency of Poland
+zloty = "PLN"
+"#;
+        let mut config: Config = toml::from_str(contents).unwrap();
+        config.alerts.push(Alert {
+            pair: "EUR/PLN".parse().unwrap(),
+            condition: Condition::Change(2.0),
+        });
+        let updated = config.update(contents).unwrap();
+        assert!(updated.contains("# Personal settings"));
+        assert!(updated.contains("precision = 4 # more than the default"));
+        assert!(updated.contains("# The currency of Poland"));
+        assert!(updated.contains("[[alerts]]"));
+        assert_eq!(toml::from_str::<Config>(&updated).unwrap(), config);
+
+        config.alerts.clear();
+        config.favorites.clear();
+        let updated = config.update(&updated).unwrap();
+        assert!(!updated.contains("alerts") && !updated.contains("favorites"));
+        assert!(updated.contains("zloty = \"PLN\""));
+        assert_eq!(toml::from_str::<Config>(&updated).unwrap(), config);
+    }
+
+    #[test]
+    fn precedence() {
+        let config: Config = toml::from_str(
+            r#"
+            precision = 4
+            format = "json"
+            # The former name of request_timeout
+            read_timeout = 5
+
+            [aliases]
+            zloty = "pln"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.precision(Some(1)), 1);
+        assert_eq!(config.precision(None), 4);
+        assert_eq!(config.cache_duration(None), DEFAULT_CACHE_DURATION);
+        assert_eq!(config.format(None), OutputFormat::Json);
+        assert_eq!(config.currency("Zloty"), "PLN");
+        assert_eq!(config.currency("eur"), "EUR");
+        assert_eq!(config.source(), SOURCES[0]);
+        assert_eq!(
+            config.http(),
+            HttpOptions {
+                request_timeout: Duration::from_secs(5),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn profiles() {
+        let mut config: Config = toml::from_str(
+            r#"
+            precision = 4
+            api_key = "personal"
+            quota = 300
+            quota_warnings = [50]
+            history_entries = 50
+
+            [profiles.work]
+            offline = true
+            history_days = 30
+            cache_duration = 60
+            quota_enforce = true
+            api_key_file = "/nonexistent"
+
+            [headers]
+            X-Team = "personal"
+            X-Client = "cli"
+
+            [profiles.home]
+            api_key = "home"
+
+            [profiles.work.aliases]
+            zloty = "PLN"
+
+            [profiles.work.headers]
+            X-Team = "rates"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.api_key(None).unwrap(), Some("personal".to_string()));
+        assert_eq!(
+            config.api_key(Some("env".to_string())).unwrap(),
+            Some("env".to_string())
+        );
+        assert!(!config.quota().enforce);
+        assert!(!config.offline(false));
+        assert!(config.offline(true));
+        assert!(config.select_profile("travel").is_err());
+
+        config.select_profile("work").unwrap();
+        assert!(config.offline(false));
+        assert_eq!(
+            config.retention(),
+            Retention {
+                max_age: 30 * 24 * 60 * 60,
+                max_entries: 50
+            }
+        );
+        assert_eq!(config.precision(None), 4);
+        assert_eq!(config.cache_duration(None), 60);
+        assert_eq!(config.currency("zloty"), "PLN");
+        assert!(config.api_key(None).is_err());
+        assert_eq!(
+            config.http().headers,
+            BTreeMap::from([
+                ("X-Client".to_string(), Secret::from("cli".to_string())),
+                ("X-Team".to_string(), Secret::from("rates".to_string()))
+            ])
+        );
+        assert_eq!(
+            config.quota(),
+            Limits {
+                budget: Some(300),
+                warnings: vec![50],
+                enforce: true
+            }
+        );
+
+        config.select_profile("home").unwrap();
+        assert_eq!(
+            config.api_key(Some("env".to_string())).unwrap(),
+            Some("home".to_string())
+        );
+    }
+}
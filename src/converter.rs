@@ -1,3 +1,5 @@
+use std::{fmt::Display, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -5,11 +7,26 @@ pub struct Converter {
     pub base: String,
     pub target: String,
     pub rate: f64,
+    pub source: String,
+    /// When the source last updated the rate, if it reports it
+    pub updated_at: Option<u64>,
 }
 
 impl Converter {
-    pub fn new(base: String, target: String, rate: f64) -> Self {
-        Converter { base, target, rate }
+    pub fn new(
+        base: String,
+        target: String,
+        rate: f64,
+        source: String,
+        updated_at: Option<u64>,
+    ) -> Self {
+        Converter {
+            base,
+            target,
+            rate,
+            source,
+            updated_at,
+        }
     }
 
     pub fn convert(&self, amount: f64) -> f64 {
@@ -17,13 +34,80 @@ impl Converter {
     }
 }
 
+/// A base and a target currency, written as `BASE/TARGET`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pair {
+    pub base: String,
+    pub target: String,
+}
+
+/// Checks that a currency code is made of letters and digits only
+pub fn is_code(code: &str) -> bool {
+    !code.is_empty() && code.chars().all(|x| x.is_alphanumeric())
+}
+
+impl FromStr for Pair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once('/') {
+            Some((base, target)) if is_code(base) && is_code(target) => Ok(Pair {
+                base: base.to_uppercase(),
+                target: target.to_uppercase(),
+            }),
+            _ => Err(format!(
+                "invalid currency pair `{}`, expected e.g. USD/EUR",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Pair {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Pair> for String {
+    fn from(value: Pair) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for Pair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.base, self.target)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn convert() {
-        let converter = Converter::new("USD".to_string(), "EUR".to_string(), 0.85);
+        let converter = Converter::new(
+            "USD".to_string(),
+            "EUR".to_string(),
+            0.85,
+            "test".to_string(),
+            None,
+        );
         assert_eq!(converter.convert(100.0), 85.0);
     }
+
+    #[test]
+    fn parse_pair() {
+        let pair: Pair = "usd/Eur".parse().unwrap();
+        assert_eq!(pair.base, "USD");
+        assert_eq!(pair.target, "EUR");
+        assert_eq!(pair.to_string(), "USD/EUR");
+        assert!("USD".parse::<Pair>().is_err());
+        assert!("USD/".parse::<Pair>().is_err());
+        assert!("US D/EUR".parse::<Pair>().is_err());
+    }
 }
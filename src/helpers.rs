@@ -1,14 +1,64 @@
-use std::{fmt::Display, process::exit};
+use std::{
+    fmt::{Debug, Display},
+    process::exit,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 
 pub fn error_and_exit<T: Display>(error: T) -> ! {
     eprintln!("{}", error);
     exit(1);
 }
 
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// A value such as an API key, which is never shown in debug output
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+/// Replaces every occurrence of the secret in a message
+pub fn redact(message: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        return message.to_string();
+    }
+    message.replace(secret, "[REDACTED]")
+}
+
 #[derive(Debug)]
 pub enum ConverterError {
     FetchError(String),
     ParseError(String),
+    Offline(String),
+    QuotaExceeded(String),
 }
 
 impl Display for ConverterError {
@@ -20,6 +70,67 @@ impl Display for ConverterError {
             ConverterError::ParseError(error) => {
                 write!(f, "Error while parsing currency data from API: {}", error)
             }
+            ConverterError::Offline(error) => {
+                write!(f, "Cannot fetch currency data in offline mode: {}", error)
+            }
+            ConverterError::QuotaExceeded(error) => {
+                write!(
+                    f,
+                    "The monthly budget of API requests is used up: {}",
+                    error
+                )
+            }
         }
     }
 }
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+pub fn format_timestamp(timestamp: u64) -> String {
+    match DateTime::<Utc>::from_timestamp(timestamp as i64, 0) {
+        Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+pub fn format_duration(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let hours = seconds % 86400 / 3600;
+    let minutes = seconds % 3600 / 60;
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m {}s", minutes, seconds % 60),
+        (0, _, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_duration_units() {
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(125), "2m 5s");
+        assert_eq!(format_duration(7260), "2h 1m");
+        assert_eq!(format_duration(90000), "1d 1h");
+    }
+
+    #[test]
+    fn secrets() {
+        let secret = Secret::from("cur_live_123".to_string());
+        assert_eq!(format!("{:?}", Some(&secret)), "Some([REDACTED])");
+        assert_eq!(secret.expose(), "cur_live_123");
+        assert_eq!(
+            redact("invalid key cur_live_123", secret.expose()),
+            "invalid key [REDACTED]"
+        );
+        assert_eq!(redact("no key", ""), "no key");
+    }
+}
@@ -1,13 +1,28 @@
-use clap::{Args, Parser, Subcommand};
-use std::env;
+use chrono::NaiveDate;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use std::{env, net::SocketAddr, path::PathBuf};
 
+use config::Config;
+use helpers::error_and_exit;
+
+mod alert;
 mod cache;
 mod commands;
+mod config;
 mod converter;
 mod helpers;
+mod metrics;
+mod quota;
+mod series;
 mod sources;
 
-use commands::{convert, interactive, list};
+use alert::Condition;
+use cache::{
+    snapshot::{MergeStrategy, SnapshotFormat},
+    Staleness,
+};
+use commands::{convert, list};
+use helpers::OutputFormat;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -15,6 +30,18 @@ use commands::{convert, interactive, list};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Only use cached rates and never make requests to the API
+    #[arg(long, global = true, env = "CURRENCY_CONVERTER_OFFLINE")]
+    offline: bool,
+
+    /// The config file to use instead of the one in the user's config directory
+    #[arg(long, global = true, env = "CURRENCY_CONVERTER_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// The profile from the config file to use, with its own settings and cache
+    #[arg(long, global = true, env = "CURRENCY_CONVERTER_PROFILE")]
+    profile: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -24,35 +51,391 @@ enum Commands {
     /// List available currencies
     List,
     /// Enter interactive mode
-    Interactive,
+    Interactive(InteractiveArgs),
+    /// Inspect and manage the cache
+    Cache(CacheArgs),
+    /// Refresh the rates of the pairs in the configured watchlist
+    Refresh(RefreshArgs),
+    /// Show the daily rates of a pair over a range of dates
+    History(HistoryArgs),
+    /// Show how much a rate changed between two points in time
+    Change(ChangeArgs),
+    /// Manage and check alerts on exchange rate movements
+    Alert(AlertArgs),
+    /// Show a live board of rates, refreshing them periodically
+    Watch(WatchArgs),
+    /// Run an HTTP server answering conversions as JSON
+    Serve(ServeArgs),
+    /// Show the API requests made this month against the quota
+    Quota,
 }
 
 #[derive(Args)]
 struct ConvertArgs {
     /// The base currency, e.g. USD
     base: String,
-    /// The target currency, e.g. EUR
+    /// The target currency, e.g. EUR, or the amount to convert into the home
+    /// currency set in the config file
+    #[arg(value_name = "TARGET|AMOUNT")]
     target: String,
     /// The amount to convert
-    amount: f64,
+    amount: Option<f64>,
+
+    /// The precision to use when displaying the result [default: 2]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_PRECISION")]
+    precision: Option<usize>,
+
+    /// The duration to cache the exchange rate for [default: 300]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_CACHE_DURATION")]
+    cache_duration: Option<u64>,
+
+    /// Which timestamp the cache duration is measured from
+    #[arg(long, value_enum, default_value_t = Staleness::Fetched)]
+    stale_by: Staleness,
+
+    /// Fall back to an expired cached rate if the API request fails,
+    /// optionally only if it is at most this many seconds old
+    #[arg(long, value_name = "MAX_AGE", num_args = 0..=1, require_equals = true)]
+    allow_stale: Option<Option<u64>>,
+
+    /// Use the rate at the end of a past date (YYYY-MM-DD) instead of the latest one
+    #[arg(short, long)]
+    date: Option<NaiveDate>,
+}
 
-    /// The precision to use when displaying the result
-    #[arg(short, long, default_value_t = 2)]
+#[derive(Args)]
+struct InteractiveArgs {
+    /// Use a full-screen terminal interface instead of line prompts
+    #[arg(long)]
+    tui: bool,
+
+    /// The duration to cache the exchange rates for [default: 300]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_CACHE_DURATION")]
+    cache_duration: Option<u64>,
+
+    /// The precision to use when displaying the result [default: 2]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_PRECISION")]
+    precision: Option<usize>,
+}
+
+#[derive(Args)]
+struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheCommands,
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Show the cached rates with their age and source
+    Show,
+    /// Remove cached rates, either all of them, for a base currency or for a pair
+    Clear {
+        /// Only remove rates with this base currency
+        base: Option<String>,
+        /// Only remove the rate for this target currency
+        target: Option<String>,
+    },
+    /// Remove cached rates fetched more than the given number of seconds ago
+    Prune {
+        /// The maximum age in seconds
+        max_age: u64,
+    },
+    /// Show the cache size and hit rate
+    Stats,
+    /// Print the path of the cache file
+    Path,
+    /// Write the cached rates to a portable snapshot
+    Export {
+        /// The file to write to, standard output if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The snapshot format, guessed from the file extension if omitted
+        #[arg(short, long, value_enum)]
+        format: Option<SnapshotFormat>,
+    },
+    /// Merge the rates from a snapshot into the cache
+    Import {
+        /// The snapshot file to read
+        file: PathBuf,
+        /// The snapshot format, guessed from the file extension if omitted
+        #[arg(short, long, value_enum)]
+        format: Option<SnapshotFormat>,
+        /// How to handle rates that are already cached
+        #[arg(short, long, value_enum, default_value_t = MergeStrategy::Newest)]
+        merge: MergeStrategy,
+    },
+}
+
+#[derive(Args)]
+struct RefreshArgs {
+    /// Only refresh pairs whose cached rate is older than this many seconds
+    /// [default: 300]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_CACHE_DURATION")]
+    cache_duration: Option<u64>,
+
+    /// The maximum number of API requests to make, in total across the
+    /// refreshes made with --interval
+    #[arg(short, long)]
+    max_requests: Option<u32>,
+
+    /// Keep running, refreshing the watchlist every this many seconds
+    #[arg(short, long)]
+    interval: Option<u64>,
+}
+
+#[derive(Args)]
+struct HistoryArgs {
+    /// The base currency, e.g. USD
+    base: String,
+    /// The target currency, e.g. EUR
+    target: String,
+
+    /// The first date of the range (YYYY-MM-DD)
+    #[arg(short, long)]
+    from: NaiveDate,
+
+    /// The last date of the range (YYYY-MM-DD), yesterday if omitted
+    #[arg(short, long)]
+    to: Option<NaiveDate>,
+
+    /// Only use rates stored locally, without fetching missing days
+    #[arg(short, long)]
+    local: bool,
+
+    /// The output format [default: table]
+    #[arg(long, value_enum, env = "CURRENCY_CONVERTER_FORMAT")]
+    format: Option<OutputFormat>,
+
+    /// The precision to use when displaying the rates
+    #[arg(short, long, default_value_t = 4)]
     precision: usize,
+}
 
-    /// The duration to cache the exchange rate for
-    #[arg(short, long, default_value_t = 300)]
-    cache_duration: u64,
+#[derive(Args)]
+struct ChangeArgs {
+    /// The base currency, e.g. USD
+    base: String,
+    /// The target currency, e.g. EUR
+    target: String,
+
+    /// Compare from the rate at the end of this date (YYYY-MM-DD) instead of
+    /// the previously stored rate
+    #[arg(short, long)]
+    from: Option<NaiveDate>,
+
+    /// Compare to the rate at the end of this date (YYYY-MM-DD) instead of
+    /// the current rate
+    #[arg(short, long, requires = "from")]
+    to: Option<NaiveDate>,
+
+    /// Also show the effect of the change on this amount
+    #[arg(short, long)]
+    amount: Option<f64>,
+
+    /// The precision to use when displaying the amounts [default: 2]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_PRECISION")]
+    precision: Option<usize>,
+
+    /// The duration to cache the current exchange rate for [default: 300]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_CACHE_DURATION")]
+    cache_duration: Option<u64>,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// The base currency, e.g. USD
+    base: String,
+    /// The target currencies, e.g. EUR GBP PLN
+    #[arg(required = true)]
+    targets: Vec<String>,
+
+    /// Refresh the rates every this many seconds
+    #[arg(short, long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+
+    /// The maximum number of API requests to make while watching
+    #[arg(short, long)]
+    max_requests: Option<u32>,
+
+    /// The precision to use when displaying the rates
+    #[arg(short, long, default_value_t = 4)]
+    precision: usize,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// The address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// The duration to cache the exchange rates for [default: 300]
+    #[arg(short, long, env = "CURRENCY_CONVERTER_CACHE_DURATION")]
+    cache_duration: Option<u64>,
+
+    /// Write new rates to the cache file every this many seconds
+    #[arg(short, long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    flush_interval: u64,
+
+    /// The number of recently used rates kept in memory
+    #[arg(short, long, default_value_t = 1000)]
+    memory_entries: usize,
+}
+
+#[derive(Args)]
+struct AlertArgs {
+    #[command(subcommand)]
+    command: AlertCommands,
+}
+
+#[derive(Subcommand)]
+enum AlertCommands {
+    /// Add an alert to the config file
+    #[command(group(ArgGroup::new("condition").required(true).args(["above", "below", "change"])))]
+    Add {
+        /// The base currency, e.g. USD
+        base: String,
+        /// The target currency, e.g. EUR
+        target: String,
+
+        /// Trigger when the rate rises above this value
+        #[arg(long)]
+        above: Option<f64>,
+
+        /// Trigger when the rate falls below this value
+        #[arg(long)]
+        below: Option<f64>,
+
+        /// Trigger when the rate moves by at least this many percent since
+        /// the previously stored rate, e.g. 2%
+        #[arg(long, value_parser = alert::parse_percent)]
+        change: Option<f64>,
+    },
+    /// List the configured alerts
+    List,
+    /// Remove an alert by its number in `alert list`
+    Remove { number: usize },
+    /// Check the alerts against the current rates, exiting with status 2 if
+    /// any of them triggered
+    Check {
+        /// The duration to cache the exchange rates for [default: 300]
+        #[arg(short, long, env = "CURRENCY_CONVERTER_CACHE_DURATION")]
+        cache_duration: Option<u64>,
+
+        /// A shell command to run when any alert triggers, overriding
+        /// `alert_command` from the config file
+        #[arg(short, long)]
+        exec: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() {
-    env::var("CURRENCY_API_KEY").expect("CURRENCY_API_KEY is not set");
     let cli = Cli::parse();
+    let needs_api = match &cli.command {
+        Commands::Cache(_) | Commands::Quota => false,
+        Commands::History(args) if args.local => false,
+        Commands::Alert(args) => matches!(args.command, AlertCommands::Check { .. }),
+        _ => true,
+    };
+    // Long-running commands check the quota with every request instead, so
+    // that they make requests again once it starts over
+    let long_running = match &cli.command {
+        Commands::Serve(_) | Commands::Watch(_) => true,
+        Commands::Refresh(args) => args.interval.is_some(),
+        _ => false,
+    };
+    let mut config = match Config::load(cli.config.as_deref()) {
+        Ok(val) => val,
+        Err(err) => error_and_exit(&err),
+    };
+    if let Some(profile) = &cli.profile {
+        if let Err(err) = config.select_profile(profile) {
+            error_and_exit(err)
+        }
+        cache::set_namespace(profile);
+    }
+
+    cache::set_retention(config.retention());
+    quota::set_limits(config.quota());
+    if let Err(err) = sources::http::configure(config.http()) {
+        error_and_exit(&err)
+    }
+    let offline = config.offline(cli.offline);
+    let offline = if !offline && needs_api && !long_running && quota::is_exhausted(config.source())
+    {
+        eprintln!(
+            "Warning: the monthly quota of API requests is used up, only using cached rates."
+        );
+        true
+    } else {
+        offline
+    };
+
+    let api_key = match config.api_key(env::var("CURRENCY_API_KEY").ok()) {
+        Ok(val) => val,
+        Err(err) => error_and_exit(&err),
+    };
+    match api_key {
+        Some(key) => sources::currency_api::set_api_key(key),
+        None if !offline && needs_api => error_and_exit(
+            "No API key is set, set the CURRENCY_API_KEY environment variable or `api_key` in the config file.",
+        ),
+        None => {}
+    }
 
     match &cli.command {
-        Commands::Convert(args) => convert(args).await,
-        Commands::List => list().await,
-        Commands::Interactive => interactive().await,
+        Commands::Convert(args) => convert(args, &config, offline).await,
+        Commands::List => list(offline).await,
+        Commands::Interactive(args) if args.tui => commands::tui::tui(args, config, offline).await,
+        Commands::Interactive(args) => commands::repl::repl(args, &config, offline).await,
+        Commands::Change(args) => commands::change::change(args, &config, offline).await,
+        Commands::History(args) => commands::history::history(args, &config, offline).await,
+        Commands::Refresh(args) => commands::refresh::refresh(args, &config, offline).await,
+        Commands::Watch(args) => commands::watch::watch(args, &config, offline).await,
+        Commands::Serve(args) => commands::serve::serve(args, config, offline).await,
+        Commands::Quota => commands::quota::quota(&config),
+        Commands::Alert(args) => match &args.command {
+            AlertCommands::Add {
+                base,
+                target,
+                above,
+                below,
+                change,
+            } => {
+                let condition = match (above, below, change) {
+                    (Some(above), _, _) => Condition::Above(*above),
+                    (_, Some(below), _) => Condition::Below(*below),
+                    (_, _, Some(change)) => Condition::Change(*change),
+                    _ => unreachable!("clap requires one of the conditions"),
+                };
+                commands::alert::add(config, base, target, condition)
+            }
+            AlertCommands::List => commands::alert::list(&config),
+            AlertCommands::Remove { number } => commands::alert::remove(config, *number),
+            AlertCommands::Check {
+                cache_duration,
+                exec,
+            } => {
+                let cache_duration = config.cache_duration(*cache_duration);
+                commands::alert::check(&config, cache_duration, exec.as_deref(), offline).await
+            }
+        },
+        Commands::Cache(args) => match &args.command {
+            CacheCommands::Show => commands::cache::show(),
+            CacheCommands::Clear { base, target } => {
+                commands::cache::clear(base.as_deref(), target.as_deref())
+            }
+            CacheCommands::Prune { max_age } => commands::cache::prune(*max_age),
+            CacheCommands::Stats => commands::cache::stats(),
+            CacheCommands::Path => commands::cache::path(),
+            CacheCommands::Export { output, format } => {
+                commands::cache::export(output.as_deref(), *format)
+            }
+            CacheCommands::Import {
+                file,
+                format,
+                merge,
+            } => commands::cache::import(file, *format, *merge),
+        },
     }
 }
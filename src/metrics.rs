@@ -0,0 +1,149 @@
+use std::{sync::OnceLock, time::Instant};
+
+use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::converter::Pair;
+
+/// The bucket boundaries of the latency histograms, in seconds
+const BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+fn handle() -> &'static PrometheusHandle {
+    HANDLE.get_or_init(|| {
+        let recorder = PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("seconds".to_string()), BUCKETS)
+            .expect("the buckets are not empty")
+            .build_recorder();
+        let handle = recorder.handle();
+        let _ = metrics::set_global_recorder(recorder);
+        describe();
+        handle
+    })
+}
+
+fn describe() {
+    describe_counter!(
+        "currency_converter_conversions_total",
+        "Conversions answered"
+    );
+    describe_counter!(
+        "currency_converter_cache_hits_total",
+        "Rates found fresh in memory or in the cache"
+    );
+    describe_counter!(
+        "currency_converter_cache_misses_total",
+        "Rates that had to be fetched or were not cached in offline mode"
+    );
+    describe_counter!(
+        "currency_converter_upstream_requests_total",
+        "Requests made to the sources, by status code"
+    );
+    describe_histogram!(
+        "currency_converter_upstream_request_duration_seconds",
+        Unit::Seconds,
+        "Duration of the requests made to the sources"
+    );
+    describe_histogram!(
+        "currency_converter_http_request_duration_seconds",
+        Unit::Seconds,
+        "Duration of the requests answered by the server"
+    );
+    describe_gauge!(
+        "currency_converter_rate",
+        "Newest cached rate of the pairs in the watchlist"
+    );
+    describe_gauge!(
+        "currency_converter_quota_remaining",
+        "Requests left in the monthly quota, as last reported by the source"
+    );
+}
+
+/// Starts recording metrics, which are discarded until this is called
+pub fn install() {
+    handle();
+}
+
+/// Renders the recorded metrics in the Prometheus text format
+pub fn render() -> String {
+    handle().render()
+}
+
+/// Records a request made to a source, with the status code of its response
+/// or `error` if there was none
+pub fn upstream_request(source: &'static str, status: Option<u16>, started: Instant) {
+    let status = status.map_or("error".to_string(), |x| x.to_string());
+    metrics::counter!(
+        "currency_converter_upstream_requests_total",
+        "source" => source,
+        "status" => status
+    )
+    .increment(1);
+    metrics::histogram!(
+        "currency_converter_upstream_request_duration_seconds",
+        "source" => source
+    )
+    .record(started.elapsed().as_secs_f64());
+}
+
+/// Records the requests left in the quota of a source
+pub fn quota_remaining(source: &'static str, remaining: f64) {
+    metrics::gauge!("currency_converter_quota_remaining", "source" => source).set(remaining);
+}
+
+pub fn conversion() {
+    metrics::counter!("currency_converter_conversions_total").increment(1);
+}
+
+/// Records a rate found fresh in a layer of the cache, `memory` or `cache`
+pub fn cache_hit(layer: &'static str) {
+    metrics::counter!("currency_converter_cache_hits_total", "layer" => layer).increment(1);
+}
+
+pub fn cache_miss() {
+    metrics::counter!("currency_converter_cache_misses_total").increment(1);
+}
+
+/// Records a request answered by the server, by its route
+pub fn http_request(path: String, status: u16, started: Instant) {
+    metrics::histogram!(
+        "currency_converter_http_request_duration_seconds",
+        "path" => path,
+        "status" => status.to_string()
+    )
+    .record(started.elapsed().as_secs_f64());
+}
+
+pub fn rate(pair: &Pair, rate: f64) {
+    metrics::gauge!(
+        "currency_converter_rate",
+        "base" => pair.base.clone(),
+        "target" => pair.target.clone()
+    )
+    .set(rate);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_upstream_requests() {
+        install();
+        upstream_request("test", Some(429), Instant::now());
+        upstream_request("test", None, Instant::now());
+        let text = render();
+        assert!(text.contains(
+            r#"currency_converter_upstream_requests_total{source="test",status="429"} 1"#
+        ));
+        assert!(text.contains(
+            r#"currency_converter_upstream_requests_total{source="test",status="error"} 1"#
+        ));
+        assert!(text.contains(
+            r#"currency_converter_upstream_request_duration_seconds_count{source="test"} 2"#
+        ));
+    }
+}
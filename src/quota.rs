@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::File,
+    io::BufReader,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{cache, helpers::ConverterError};
+
+/// The percentages of the budget at which to warn when none are configured
+pub const DEFAULT_WARNINGS: [u64; 2] = [80, 100];
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+/// Held while updating the quota file, which concurrent requests of the
+/// server would otherwise overwrite
+static FILE: Mutex<()> = Mutex::new(());
+
+/// Sets the budget every request is counted against, resolved from the config
+/// file at startup
+pub fn set_limits(limits: Limits) {
+    let _ = LIMITS.set(limits);
+}
+
+fn limits() -> &'static Limits {
+    LIMITS.get_or_init(Limits::default)
+}
+
+/// The monthly budget of requests to a source
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub budget: Option<u64>,
+    /// The percentages of the budget at which to warn
+    pub warnings: Vec<u64>,
+    /// Whether to refuse requests once the budget is used up
+    pub enforce: bool,
+}
+
+impl Limits {
+    /// Returns the highest warning percentage reached by the request with the
+    /// given number, if any. Several percentages can be reached at once with
+    /// a small budget
+    fn warning(&self, used: u64) -> Option<u64> {
+        let budget = self.budget?;
+        let threshold = |percent: u64| (budget * percent).div_ceil(100);
+        self.warnings
+            .iter()
+            .copied()
+            .filter(|percent| {
+                used.saturating_sub(1) < threshold(*percent) && threshold(*percent) <= used
+            })
+            .max()
+    }
+
+    fn is_exhausted(&self, used: u64) -> bool {
+        matches!(self.budget, Some(budget) if self.enforce && used >= budget)
+    }
+}
+
+/// The requests made to each source, persisted next to the cache
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Quota {
+    #[serde(default)]
+    sources: BTreeMap<String, Usage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Usage {
+    /// The month the requests were made in, e.g. 2024-03
+    month: String,
+    requests: u64,
+}
+
+impl Quota {
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        cache::data_path("quota")
+    }
+
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let file = File::open(Self::path()?)?;
+        let quota: Quota = serde_json::from_reader(BufReader::new(file))?;
+        Ok(quota)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let file = File::create(Self::path()?)?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// The number of requests made to the source in the month
+    pub fn used(&self, source: &str, month: &str) -> u64 {
+        match self.sources.get(source) {
+            Some(usage) if usage.month == month => usage.requests,
+            _ => 0,
+        }
+    }
+
+    /// Counts a request, starting over in a new month, and returns the number
+    /// of requests made in the month so far
+    fn add(&mut self, source: &str, month: &str) -> u64 {
+        let usage = self
+            .sources
+            .entry(source.to_string())
+            .or_insert_with(|| Usage {
+                month: month.to_string(),
+                requests: 0,
+            });
+        if usage.month != month {
+            usage.month = month.to_string();
+            usage.requests = 0;
+        }
+        usage.requests += 1;
+        usage.requests
+    }
+}
+
+pub fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+/// Checks whether requests to the source are refused for the rest of the month
+pub fn is_exhausted(source: &str) -> bool {
+    limits().is_exhausted(
+        Quota::load()
+            .unwrap_or_default()
+            .used(source, &current_month()),
+    )
+}
+
+/// Counts a request about to be made to a source, refusing it if the budget
+/// is used up and enforced. The quota file is updated on a blocking thread so
+/// that waiting for it does not hold up the runtime
+pub async fn record(source: &'static str) -> Result<(), ConverterError> {
+    tokio::task::spawn_blocking(move || record_blocking(source))
+        .await
+        .expect("the quota could not be updated")
+}
+
+fn record_blocking(source: &str) -> Result<(), ConverterError> {
+    let limits = limits();
+    let _file = FILE.lock().unwrap();
+    let mut quota = Quota::load().unwrap_or_default();
+    let month = current_month();
+    if limits.is_exhausted(quota.used(source, &month)) {
+        return Err(ConverterError::QuotaExceeded(format!(
+            "all {} requests to {} for {} were made",
+            limits.budget.unwrap_or_default(),
+            source,
+            month
+        )));
+    }
+
+    let used = quota.add(source, &month);
+    if let Err(err) = quota.save() {
+        eprintln!(
+            "Warning: could not save the number of API requests: {}",
+            err
+        );
+    }
+    if let (Some(percent), Some(budget)) = (limits.warning(used), limits.budget) {
+        eprintln!(
+            "Warning: {} of the {} requests to {} for {} were made ({}%).",
+            used, budget, source, month, percent
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monthly_reset() {
+        let mut quota = Quota::default();
+        assert_eq!(quota.add("test", "2024-03"), 1);
+        assert_eq!(quota.add("test", "2024-03"), 2);
+        assert_eq!(quota.used("test", "2024-03"), 2);
+        assert_eq!(quota.used("test", "2024-04"), 0);
+        assert_eq!(quota.add("test", "2024-04"), 1);
+        assert_eq!(quota.used("other", "2024-04"), 0);
+    }
+
+    #[test]
+    fn warnings() {
+        let limits = Limits {
+            budget: Some(300),
+            warnings: DEFAULT_WARNINGS.to_vec(),
+            enforce: false,
+        };
+        assert_eq!(limits.warning(239), None);
+        assert_eq!(limits.warning(240), Some(80));
+        assert_eq!(limits.warning(300), Some(100));
+        assert!(!limits.is_exhausted(300));
+
+        let limits = Limits {
+            enforce: true,
+            ..limits
+        };
+        assert!(!limits.is_exhausted(299));
+        assert!(limits.is_exhausted(300));
+        assert!(!Limits::default().is_exhausted(1000));
+
+        // 80% and 100% of 2 requests are both reached by the second one
+        let limits = Limits {
+            budget: Some(2),
+            ..limits
+        };
+        assert_eq!(limits.warning(1), None);
+        assert_eq!(limits.warning(2), Some(100));
+        assert_eq!(limits.warning(3), None);
+    }
+}
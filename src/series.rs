@@ -0,0 +1,227 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::helpers::OutputFormat;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Daily rates for a pair, oldest first
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Series {
+    pub base: String,
+    pub target: String,
+    pub rates: Vec<DailyRate>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct DailyRate {
+    pub date: NaiveDate,
+    pub rate: f64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct SeriesStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// The standard deviation of the day-to-day changes, in percent
+    pub volatility: f64,
+}
+
+/// The movement of a rate between two points in time
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RateChange {
+    pub from: f64,
+    pub to: f64,
+    pub absolute: f64,
+    pub percent: f64,
+}
+
+impl RateChange {
+    pub fn new(from: f64, to: f64) -> Self {
+        RateChange {
+            from,
+            to,
+            absolute: to - from,
+            percent: (to / from - 1.0) * 100.0,
+        }
+    }
+}
+
+impl Series {
+    pub fn new(
+        base: String,
+        target: String,
+        rates: impl IntoIterator<Item = (NaiveDate, f64)>,
+    ) -> Self {
+        let mut rates: Vec<DailyRate> = rates
+            .into_iter()
+            .map(|(date, rate)| DailyRate { date, rate })
+            .collect();
+        rates.sort_by_key(|x| x.date);
+        Series {
+            base,
+            target,
+            rates,
+        }
+    }
+
+    pub fn stats(&self) -> Option<SeriesStats> {
+        let first = self.rates.first()?.rate;
+        let (min, max, sum) = self
+            .rates
+            .iter()
+            .fold((first, first, 0.0), |(min, max, sum), x| {
+                (min.min(x.rate), max.max(x.rate), sum + x.rate)
+            });
+
+        let changes: Vec<f64> = self
+            .rates
+            .windows(2)
+            .map(|x| (x[1].rate / x[0].rate - 1.0) * 100.0)
+            .collect();
+        let volatility = if changes.len() < 2 {
+            0.0
+        } else {
+            let mean = changes.iter().sum::<f64>() / changes.len() as f64;
+            let variance = changes.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+                / (changes.len() - 1) as f64;
+            variance.sqrt()
+        };
+
+        Some(SeriesStats {
+            min,
+            max,
+            mean: sum / self.rates.len() as f64,
+            volatility,
+        })
+    }
+
+    pub fn sparkline(&self) -> String {
+        let stats = match self.stats() {
+            Some(val) => val,
+            None => return String::new(),
+        };
+        let range = stats.max - stats.min;
+        self.rates
+            .iter()
+            .map(|x| {
+                if range == 0.0 {
+                    SPARKS[SPARKS.len() / 2]
+                } else {
+                    let index = ((x.rate - stats.min) / range * (SPARKS.len() - 1) as f64).round();
+                    SPARKS[index as usize]
+                }
+            })
+            .collect()
+    }
+
+    pub fn render(&self, format: OutputFormat, precision: usize) -> String {
+        match format {
+            OutputFormat::Table => {
+                let mut table = format!("{:<10}  {}\n", "Date", "Rate");
+                for rate in &self.rates {
+                    table.push_str(&format!(
+                        "{}  {:.precision$}\n",
+                        rate.date,
+                        rate.rate,
+                        precision = precision
+                    ));
+                }
+                if let Some(stats) = self.stats() {
+                    table.push_str(&format!(
+                        "\n{}/{} {}\nMin {:.precision$}  Max {:.precision$}  Mean {:.precision$}  Volatility {:.2}% per day\n",
+                        self.base,
+                        self.target,
+                        self.sparkline(),
+                        stats.min,
+                        stats.max,
+                        stats.mean,
+                        stats.volatility,
+                        precision = precision
+                    ));
+                }
+                table
+            }
+            OutputFormat::Json => {
+                let json = serde_json::json!({
+                    "base": self.base,
+                    "target": self.target,
+                    "rates": self.rates,
+                    "stats": self.stats(),
+                });
+                format!(
+                    "{}\n",
+                    serde_json::to_string_pretty(&json).unwrap_or_default()
+                )
+            }
+            OutputFormat::Csv => {
+                let mut csv = "date,rate\n".to_string();
+                for rate in &self.rates {
+                    csv.push_str(&format!("{},{}\n", rate.date, rate.rate));
+                }
+                csv
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(rates: &[f64]) -> Series {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        Series::new(
+            "USD".to_string(),
+            "EUR".to_string(),
+            rates
+                .iter()
+                .enumerate()
+                .map(|(day, rate)| (start + chrono::Days::new(day as u64), *rate)),
+        )
+    }
+
+    #[test]
+    fn stats() {
+        let stats = series(&[1.0, 1.1, 0.99, 1.0]).stats().unwrap();
+        assert_eq!(stats.min, 0.99);
+        assert_eq!(stats.max, 1.1);
+        assert!((stats.mean - 1.0225).abs() < 1e-9);
+        assert!((stats.volatility - 10.0).abs() < 0.2);
+        assert_eq!(series(&[1.0]).stats().unwrap().volatility, 0.0);
+        assert_eq!(series(&[]).stats(), None);
+    }
+
+    #[test]
+    fn sparkline() {
+        assert_eq!(series(&[1.0, 1.5, 2.0, 1.0]).sparkline(), "▁▅█▁");
+        assert_eq!(series(&[1.0, 1.0]).sparkline(), "▅▅");
+        assert_eq!(series(&[]).sparkline(), "");
+    }
+
+    #[test]
+    fn rate_change() {
+        let change = RateChange::new(4.0, 4.1);
+        assert!((change.absolute - 0.1).abs() < 1e-9);
+        assert!((change.percent - 2.5).abs() < 1e-9);
+        assert!(RateChange::new(4.0, 3.0).percent < 0.0);
+    }
+
+    #[test]
+    fn render_csv() {
+        assert_eq!(
+            series(&[1.0, 1.5]).render(OutputFormat::Csv, 2),
+            "date,rate\n2024-01-01,1\n2024-01-02,1.5\n"
+        );
+    }
+
+    #[test]
+    fn render_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&series(&[1.0, 1.5]).render(OutputFormat::Json, 2)).unwrap();
+        assert_eq!(json["rates"][1]["date"], "2024-01-02");
+        assert_eq!(json["rates"][1]["rate"], 1.5);
+        assert_eq!(json["stats"]["max"], 1.5);
+    }
+}
@@ -1,14 +1,54 @@
 use std::fmt::Display;
 
-use crate::converter::Converter;
+use chrono::NaiveDate;
+
+use crate::{converter::Converter, helpers::ConverterError};
 
 pub mod currency_api;
+pub mod http;
+
+/// The names of the available sources
+pub const SOURCES: [&str; 1] = [currency_api::CurrencyApi::NAME];
 
 pub trait ConverterDataSource {
+    /// Name recorded alongside every rate obtained from this source
+    const NAME: &'static str;
+
     async fn load(base: &str, target: &str) -> Result<Converter, crate::helpers::ConverterError>;
+    /// Loads the rates for several targets sharing a base in as few requests
+    /// as the source allows
+    async fn load_many(
+        base: &str,
+        targets: &[String],
+    ) -> Result<Vec<Converter>, crate::helpers::ConverterError>;
+    /// Loads the rate at the end of a past date
+    async fn historical(
+        base: &str,
+        target: &str,
+        date: NaiveDate,
+    ) -> Result<Converter, crate::helpers::ConverterError>;
+    /// Loads the daily rates between two past dates, inclusive
+    async fn timeseries(
+        base: &str,
+        target: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, Converter)>, crate::helpers::ConverterError>;
     async fn list() -> Result<CurrencyList, crate::helpers::ConverterError>;
 }
 
+/// Loads a rate from the source with the given name, one of `SOURCES`
+pub async fn load(source: &str, base: &str, target: &str) -> Result<Converter, ConverterError> {
+    if source == currency_api::CurrencyApi::NAME {
+        return currency_api::CurrencyApi::load(base, target).await;
+    }
+    Err(ConverterError::FetchError(format!(
+        "Unknown source `{}`, available sources: {}",
+        source,
+        SOURCES.join(", ")
+    )))
+}
+
 pub struct CurrencyList {
     pub currencies: Vec<String>,
 }
@@ -1,11 +1,33 @@
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, sync::OnceLock};
 
+use chrono::{DateTime, NaiveDate};
 use reqwest::Response;
 use serde::{Deserialize, Serialize};
 
-use crate::{converter::Converter, helpers::ConverterError};
+use crate::{
+    converter::Converter,
+    helpers::{redact, ConverterError},
+    metrics,
+};
 
-use super::{ConverterDataSource, CurrencyList};
+use super::{http, ConverterDataSource, CurrencyList};
+
+static API_KEY: OnceLock<String> = OnceLock::new();
+
+/// Sets the key sent with every request, resolved from the environment or the
+/// config file at startup
+pub fn set_api_key(key: String) {
+    let _ = API_KEY.set(key);
+}
+
+fn api_key() -> &'static str {
+    API_KEY.get().map_or("", String::as_str)
+}
+
+/// Describes a request error without the API key, should it appear in it
+fn describe(error: reqwest::Error) -> String {
+    redact(&error.to_string(), api_key())
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Meta {
@@ -13,12 +35,32 @@ pub struct Meta {
     base: Option<String>,
 }
 
+impl Meta {
+    fn updated_at(&self) -> Option<u64> {
+        DateTime::parse_from_rfc3339(&self.last_updated_at)
+            .ok()
+            .and_then(|datetime| u64::try_from(datetime.timestamp()).ok())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Currency {
     code: String,
     value: f64,
 }
 
+/// The rates for a single day, as returned by the range endpoint
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RangeDay {
+    datetime: String,
+    currencies: HashMap<String, Currency>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RangeResponse {
+    data: Vec<RangeDay>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CurrencyApi {
     pub meta: Meta,
@@ -26,55 +68,64 @@ pub struct CurrencyApi {
 }
 
 impl CurrencyApi {
-    async fn fetch(base: &str, target: &str) -> Result<Self, ConverterError> {
-        let url = format!(
-            "https://api.currencyapi.com/v3/latest?apikey={}&currencies={}&base_currency={}",
-            env::var("CURRENCY_API_KEY").expect("CURRENCY_API_KEY is not set"),
-            target,
-            base
-        );
-        let request = reqwest::get(&url).await;
-        let response: Response = match request {
-            Ok(val) => {
-                match val.status() {
-                    reqwest::StatusCode::OK => val,
-                    status_code => match status_code {
-                        reqwest::StatusCode::UNPROCESSABLE_ENTITY => {
-                            return Err(ConverterError::FetchError(format!(
-                                    "The request was invalid. Please check that your inputs were correct and are supported: {} -> {}",
-                                    base, target
-                                )));
-                        }
-                        reqwest::StatusCode::TOO_MANY_REQUESTS => {
-                            return Err(ConverterError::FetchError("You have reached the rate limit for the API. Please try again later.".to_string()));
-                        }
-                        reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
-                            return Err(ConverterError::FetchError("There was an error on the API server side. Please try again later.".to_string()));
-                        }
-                        reqwest::StatusCode::UNAUTHORIZED => {
-                            return Err(ConverterError::FetchError("The API key is invalid. Please check that the CURRENCY_API_KEY environment variable is set correctly.".to_string()));
-                        }
-                        _ => {
-                            return Err(ConverterError::FetchError(format!(
-                                "There was a network error while fetching data from the API, status code: {}",
-                                status_code
-                            )));
-                        }
-                    },
-                }
-            }
-            Err(err) => {
-                return Err(ConverterError::FetchError(format!(
-                    "There was a network error while fetching data from the API: {}",
-                    err
-                )));
+    /// Makes a request authenticated with the API key, which is sent in a
+    /// header so that it does not end up in the logs of proxies, recording
+    /// the quota left reported by the API
+    async fn request(url: &str) -> Result<Response, ConverterError> {
+        let request = http::get(Self::NAME, url, &[("apikey", api_key())]).await;
+        if let Ok(response) = &request {
+            let remaining = response
+                .headers()
+                .get("X-RateLimit-Remaining-Quota-Month")
+                .and_then(|x| x.to_str().ok())
+                .and_then(|x| x.parse().ok());
+            if let Some(remaining) = remaining {
+                metrics::quota_remaining(Self::NAME, remaining);
             }
+        }
+        request
+    }
+
+    async fn get(url: &str, base: &str, target: &str) -> Result<Response, ConverterError> {
+        let request = Self::request(url).await;
+        match request {
+            Ok(val) => match val.status() {
+                reqwest::StatusCode::OK => Ok(val),
+                reqwest::StatusCode::UNPROCESSABLE_ENTITY => Err(ConverterError::FetchError(format!(
+                    "The request was invalid. Please check that your inputs were correct and are supported: {} -> {}",
+                    base, target
+                ))),
+                reqwest::StatusCode::TOO_MANY_REQUESTS => Err(ConverterError::FetchError("You have reached the rate limit for the API. Please try again later.".to_string())),
+                reqwest::StatusCode::INTERNAL_SERVER_ERROR => Err(ConverterError::FetchError("There was an error on the API server side. Please try again later.".to_string())),
+                reqwest::StatusCode::UNAUTHORIZED => Err(ConverterError::FetchError("The API key is invalid. Please check the CURRENCY_API_KEY environment variable or the API key in the config file.".to_string())),
+                status_code => Err(ConverterError::FetchError(format!(
+                    "There was a network error while fetching data from the API, status code: {}",
+                    status_code
+                ))),
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn fetch(
+        base: &str,
+        target: &str,
+        date: Option<NaiveDate>,
+    ) -> Result<Self, ConverterError> {
+        let endpoint = match date {
+            Some(date) => format!("historical?date={}&", date.format("%Y-%m-%d")),
+            None => "latest?".to_string(),
         };
+        let url = format!(
+            "https://api.currencyapi.com/v3/{}currencies={}&base_currency={}",
+            endpoint, target, base
+        );
+        let response = Self::get(&url, base, target).await?;
 
         let mut currency_data: CurrencyApi = match response.json().await {
             Ok(val) => val,
             Err(error) => {
-                return Err(ConverterError::ParseError(error.to_string()));
+                return Err(ConverterError::ParseError(describe(error)));
             }
         };
 
@@ -83,7 +134,29 @@ impl CurrencyApi {
         Ok(currency_data)
     }
 
+    async fn fetch_range(
+        base: &str,
+        target: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<RangeResponse, ConverterError> {
+        let url = format!(
+            "https://api.currencyapi.com/v3/range?currencies={}&base_currency={}&datetime_start={}T00:00:00Z&datetime_end={}T23:59:59Z&accuracy=day",
+            target,
+            base,
+            from.format("%Y-%m-%d"),
+            to.format("%Y-%m-%d")
+        );
+        let response = Self::get(&url, base, target).await?;
+
+        match response.json().await {
+            Ok(val) => Ok(val),
+            Err(error) => Err(ConverterError::ParseError(describe(error))),
+        }
+    }
+
     fn into_converter(self) -> Converter {
+        let updated_at = self.meta.updated_at();
         let base = self
             .meta
             .base
@@ -103,22 +176,86 @@ impl CurrencyApi {
             .expect("The rate for the target currency was not set in the API response.")
             .value;
 
-        Converter::new(base, target, rate)
+        Converter::new(base, target, rate, Self::NAME.to_string(), updated_at)
+    }
+
+    fn into_converters(self) -> Vec<Converter> {
+        let updated_at = self.meta.updated_at();
+        let base = self
+            .meta
+            .base
+            .expect("The base currency was not set in the API response.");
+
+        self.data
+            .into_values()
+            .map(|currency| {
+                Converter::new(
+                    base.clone(),
+                    currency.code,
+                    currency.value,
+                    Self::NAME.to_string(),
+                    updated_at,
+                )
+            })
+            .collect()
+    }
+}
+
+impl RangeResponse {
+    fn into_converters(self, base: &str, target: &str) -> Vec<(NaiveDate, Converter)> {
+        self.data
+            .into_iter()
+            .filter_map(|day| {
+                let datetime = DateTime::parse_from_rfc3339(&day.datetime).ok()?;
+                let rate = day.currencies.get(target)?.value;
+                let converter = Converter::new(
+                    base.to_string(),
+                    target.to_string(),
+                    rate,
+                    CurrencyApi::NAME.to_string(),
+                    u64::try_from(datetime.timestamp()).ok(),
+                );
+                Some((datetime.date_naive(), converter))
+            })
+            .collect()
     }
 }
 
 impl ConverterDataSource for CurrencyApi {
+    const NAME: &'static str = "currencyapi";
+
     async fn load(base: &str, target: &str) -> Result<Converter, crate::helpers::ConverterError> {
-        let api_data = CurrencyApi::fetch(base, target).await?;
+        let api_data = CurrencyApi::fetch(base, target, None).await?;
+        Ok(api_data.into_converter())
+    }
+
+    async fn load_many(base: &str, targets: &[String]) -> Result<Vec<Converter>, ConverterError> {
+        let api_data = CurrencyApi::fetch(base, &targets.join(","), None).await?;
+        Ok(api_data.into_converters())
+    }
+
+    async fn historical(
+        base: &str,
+        target: &str,
+        date: NaiveDate,
+    ) -> Result<Converter, ConverterError> {
+        let api_data = CurrencyApi::fetch(base, target, Some(date)).await?;
         Ok(api_data.into_converter())
     }
 
+    async fn timeseries(
+        base: &str,
+        target: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, Converter)>, ConverterError> {
+        let range = CurrencyApi::fetch_range(base, target, from, to).await?;
+        Ok(range.into_converters(base, target))
+    }
+
     async fn list() -> Result<CurrencyList, ConverterError> {
-        let url = format!(
-            "https://api.currencyapi.com/v3/currencies?apikey={}",
-            env::var("CURRENCY_API_KEY").expect("CURRENCY_API_KEY is not set")
-        );
-        let request = reqwest::get(&url).await;
+        let url = "https://api.currencyapi.com/v3/currencies";
+        let request = Self::request(url).await;
         let response: Response = match request {
             Ok(val) => {
                 match val.status() {
@@ -131,7 +268,7 @@ impl ConverterDataSource for CurrencyApi {
                             return Err(ConverterError::FetchError("There was an error on the API server side. Please try again later.".to_string()));
                         }
                         reqwest::StatusCode::UNAUTHORIZED => {
-                            return Err(ConverterError::FetchError("The API key is invalid. Please check that the CURRENCY_API_KEY environment variable is set correctly.".to_string()));
+                            return Err(ConverterError::FetchError("The API key is invalid. Please check the CURRENCY_API_KEY environment variable or the API key in the config file.".to_string()));
                         }
                         _ => {
                             return Err(ConverterError::FetchError(format!(
@@ -142,12 +279,7 @@ impl ConverterDataSource for CurrencyApi {
                     },
                 }
             }
-            Err(err) => {
-                return Err(ConverterError::FetchError(format!(
-                    "There was a network error while fetching data from the API: {}",
-                    err
-                )));
-            }
+            Err(err) => return Err(err),
         };
         let json = response.json::<serde_json::Value>().await;
 
@@ -161,7 +293,7 @@ impl ConverterDataSource for CurrencyApi {
                 }
             },
             Err(err) => {
-                return Err(ConverterError::ParseError(err.to_string()));
+                return Err(ConverterError::ParseError(describe(err)));
             }
         };
 
@@ -174,11 +306,10 @@ impl ConverterDataSource for CurrencyApi {
 mod tests {
     use super::*;
 
-    #[test]
-    fn into_converter() {
-        let api_data = CurrencyApi {
+    fn api_data() -> CurrencyApi {
+        CurrencyApi {
             meta: Meta {
-                last_updated_at: "2021-01-01".to_string(),
+                last_updated_at: "2021-01-01T23:59:59Z".to_string(),
                 base: Some("USD".to_string()),
             },
             data: {
@@ -192,11 +323,62 @@ mod tests {
                 );
                 map
             },
-        };
+        }
+    }
 
-        let converter = api_data.into_converter();
+    #[test]
+    fn into_converter() {
+        let converter = api_data().into_converter();
         assert_eq!(converter.base, "USD");
         assert_eq!(converter.target, "EUR");
         assert_eq!(converter.rate, 0.85);
+        assert_eq!(converter.source, CurrencyApi::NAME);
+        assert_eq!(converter.updated_at, Some(1609545599));
+    }
+
+    #[test]
+    fn into_converters() {
+        let mut api_data = api_data();
+        api_data.data.insert(
+            "GBP".to_string(),
+            Currency {
+                code: "GBP".to_string(),
+                value: 0.75,
+            },
+        );
+
+        let mut converters = api_data.into_converters();
+        converters.sort_by(|a, b| a.target.cmp(&b.target));
+        assert_eq!(converters.len(), 2);
+        assert_eq!(converters[0].target, "EUR");
+        assert_eq!(converters[1].target, "GBP");
+        assert_eq!(converters[1].rate, 0.75);
+        assert!(converters.iter().all(|x| x.base == "USD"));
+        assert!(converters.iter().all(|x| x.updated_at == Some(1609545599)));
+    }
+
+    #[test]
+    fn range_into_converters() {
+        let range: RangeResponse = serde_json::from_str(
+            r#"{"data": [
+                {"datetime": "2024-01-01T23:59:59Z", "currencies": {"EUR": {"code": "EUR", "value": 0.9}}},
+                {"datetime": "2024-01-02T23:59:59Z", "currencies": {"GBP": {"code": "GBP", "value": 0.8}}},
+                {"datetime": "2024-01-03T23:59:59Z", "currencies": {"EUR": {"code": "EUR", "value": 0.91}}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let converters = range.into_converters("USD", "EUR");
+        assert_eq!(converters.len(), 2);
+        assert_eq!(
+            converters[0].0,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+        );
+        assert_eq!(converters[0].1.rate, 0.9);
+        assert_eq!(
+            converters[1].0,
+            NaiveDate::from_ymd_opt(2024, 1, 3).unwrap()
+        );
+        assert_eq!(converters[1].1.base, "USD");
     }
 }